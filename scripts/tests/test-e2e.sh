@@ -1,9 +1,16 @@
 #!/bin/bash
+set -e
 
-# Build wasm32-unknown-unknown binary
-# cargo build --locked --release --target wasm32-unknown-unknown
-cargo stylus check
-
-# Run tests
 export RPC_URL=http://localhost:8547
-cargo test --locked --test "integration_tests" --features export-abi -- --nocapture
\ No newline at end of file
+
+# Every optional feature is size-checked and tested in its own build, next to
+# the build without any.
+for features in "" bonding-curves dutch-auction batch-auction overflow-sale chainlink \
+    "allowlist vouchers" permit permit2; do
+    # Build wasm32-unknown-unknown binary
+    cargo build --locked --release --target wasm32-unknown-unknown --features "$features"
+    cargo stylus check --wasm-file target/wasm32-unknown-unknown/release/stylus_counter.wasm
+
+    # Run tests
+    cargo test --locked --test "integration_tests" --features "export-abi $features" -- --nocapture
+done
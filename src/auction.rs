@@ -0,0 +1,190 @@
+//! Uniform-price batch auction clearing. Bids are grouped into price levels
+//! with 18-decimal USD amounts and prices per whole sale token, and cleared
+//! one level at a time so settlement can be split across transactions.
+
+use stylus_sdk::alloy_primitives::U256;
+
+use crate::{constants::WAD, errors::TokenSaleErrors, math};
+
+/// Clears the auction at the price level `price` if the bids at or above it
+/// demand the whole `supply`, returning the clearing price and the share of
+/// their bid, as a WAD fraction, that bids priced exactly at it are filled
+/// with. Bids above the clearing price are filled in full and bids below it
+/// not at all.
+///
+/// Levels are visited from the highest price down: `higher` is the USD
+/// committed above `price` and `group` the USD committed at it.
+pub fn clear_level(
+    higher: U256,
+    group: U256,
+    price: U256,
+    supply: U256,
+    unit: U256,
+) -> Result<Option<(U256, U256)>, TokenSaleErrors> {
+    let demand = math::mul_div(math::add(higher, group)?, unit, price)?;
+    if demand < supply {
+        return Ok(None);
+    }
+
+    // The bids above this level alone clear at a higher price. Rounding up
+    // can land exactly on the previous level's price, whose bids must then
+    // still be filled in full.
+    let higher_price = math::mul_div_up(higher, unit, supply)?;
+    if higher_price > price {
+        return Ok(Some((higher_price, WAD)));
+    }
+
+    let remaining = math::sub(supply, math::mul_div(higher, unit, price)?)?;
+    if remaining.is_zero() {
+        return Ok(Some((price, U256::ZERO)));
+    }
+
+    // `remaining` and `group_demand` are rounded separately, so the level can
+    // seem to demand less than what is left, or nothing at all with few
+    // decimals. Either way it is filled in full.
+    let group_demand = math::mul_div(group, unit, price)?;
+    if remaining >= group_demand {
+        return Ok(Some((price, WAD)));
+    }
+
+    let fill = math::mul_div(remaining, WAD, group_demand)?;
+
+    Ok(Some((price, fill.min(WAD))))
+}
+
+/// Clears the auction once every level has been visited without demanding
+/// the whole `supply`: every bid is filled, at the price where the `higher`
+/// USD committed demands exactly `supply` if that is above the reserve.
+pub fn clear_remaining(
+    higher: U256,
+    supply: U256,
+    unit: U256,
+    reserve: U256,
+) -> Result<(U256, U256), TokenSaleErrors> {
+    let price = math::mul_div_up(higher, unit, supply)?;
+
+    Ok((price.max(reserve), WAD))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::cmp::Reverse;
+
+    use super::*;
+
+    /// Clears `(usd_value, max_price)` bids in one go, the way `settle()`
+    /// walks the price levels.
+    fn clear(
+        bids: &[(U256, U256)],
+        supply: U256,
+        unit: U256,
+        reserve: U256,
+    ) -> Result<(U256, U256), TokenSaleErrors> {
+        let mut bids: Vec<(U256, U256)> = bids.to_vec();
+        bids.sort_unstable_by_key(|bid| Reverse(bid.1));
+
+        let mut higher = U256::ZERO;
+        let mut i = 0;
+        while i < bids.len() {
+            let price = bids[i].1;
+            let mut group = U256::ZERO;
+            while i < bids.len() && bids[i].1 == price {
+                group = math::add(group, bids[i].0)?;
+                i += 1;
+            }
+
+            if let Some(cleared) = clear_level(higher, group, price, supply, unit)? {
+                return Ok(cleared);
+            }
+
+            higher = math::add(higher, group)?;
+        }
+
+        clear_remaining(higher, supply, unit, reserve)
+    }
+
+    fn wad(x: u64) -> U256 {
+        U256::from(x) * WAD
+    }
+
+    #[test]
+    fn undersubscribed_auction_clears_at_the_reserve() {
+        let bids = [(wad(500), wad(2))];
+
+        assert_eq!(clear(&bids, wad(1_000), WAD, WAD).ok(), Some((WAD, WAD)));
+        assert_eq!(
+            clear(&bids, wad(1_000), WAD, U256::ZERO).ok(),
+            Some((WAD / U256::from(2), WAD))
+        );
+        assert_eq!(clear(&[], wad(1_000), WAD, WAD).ok(), Some((WAD, WAD)));
+    }
+
+    #[test]
+    fn marginal_bids_are_filled_pro_rata() {
+        // $1500 at $1 for 1000 tokens.
+        let bids = [(wad(500), WAD), (wad(1_000), WAD)];
+        assert_eq!(
+            clear(&bids, wad(1_000), WAD, WAD).ok(),
+            Some((WAD, WAD * U256::from(2) / U256::from(3)))
+        );
+
+        // The $2 bid takes 250 tokens, the $1 bid half of its 1000.
+        let bids = [(wad(1_000), WAD), (wad(500), wad(2))];
+        assert_eq!(
+            clear(&bids, wad(1_000), WAD, U256::ZERO).ok(),
+            Some((WAD, WAD / U256::from(2)))
+        );
+    }
+
+    #[test]
+    fn higher_bids_alone_can_set_the_price() {
+        // $3000 at up to $5 buys all 1000 tokens at $3.
+        let bids = [(wad(1_000), WAD), (wad(3_000), wad(5))];
+
+        assert_eq!(
+            clear(&bids, wad(1_000), WAD, U256::ZERO).ok(),
+            Some((wad(3), WAD))
+        );
+    }
+
+    #[test]
+    fn higher_bids_clearing_at_their_own_price_are_filled() {
+        // One wei short of demanding all 1000 tokens at $1, so the $1 bid
+        // alone clears at a price that rounds up to exactly $1.
+        let bids = [
+            (wad(1_000) - U256::from(1), WAD),
+            (wad(100), WAD / U256::from(2)),
+        ];
+
+        assert_eq!(
+            clear(&bids, wad(1_000), WAD, U256::ZERO).ok(),
+            Some((WAD, WAD))
+        );
+    }
+
+    #[test]
+    fn fill_never_exceeds_the_whole_bid() {
+        // Demand at $3 is exactly the supply, but the rounded share left for
+        // the $3 bid is one wei above what it demands.
+        let bids = [(wad(2), wad(4)), (wad(2), wad(3))];
+        let supply = wad(4) / U256::from(3);
+
+        assert_eq!(
+            clear(&bids, supply, WAD, U256::ZERO).ok(),
+            Some((wad(3), WAD))
+        );
+    }
+
+    #[test]
+    fn levels_demanding_less_than_a_unit_are_filled() {
+        // With a 0-decimal token the $1 bid at $2 demands no whole token.
+        let bids = [(wad(3), wad(4)), (WAD, wad(2))];
+        let unit = U256::from(1);
+
+        assert_eq!(
+            clear(&bids, U256::from(2), unit, U256::ZERO).ok(),
+            Some((wad(2), WAD))
+        );
+    }
+}
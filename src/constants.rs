@@ -1 +1,51 @@
+use stylus_sdk::alloy_primitives::{address, Address, U256};
+
 pub const OWNER: &str = "0xdeadbeefdeadbeefdeadbeefdeadbeefdeadbeeftest";
+
+/// Optional features built into the contract, see `[features]` in
+/// `Cargo.toml`. Everything behind a missing feature is left out of the
+/// binary: its modes are rejected and its entry points fail with
+/// `FeatureDisabled`.
+pub const BONDING_CURVES_ENABLED: bool = cfg!(feature = "bonding-curves");
+pub const DUTCH_AUCTION_ENABLED: bool = cfg!(feature = "dutch-auction");
+pub const BATCH_AUCTION_ENABLED: bool = cfg!(feature = "batch-auction");
+pub const OVERFLOW_SALE_ENABLED: bool = cfg!(feature = "overflow-sale");
+pub const CHAINLINK_ENABLED: bool = cfg!(feature = "chainlink");
+pub const ALLOWLIST_ENABLED: bool = cfg!(feature = "allowlist");
+pub const VOUCHERS_ENABLED: bool = cfg!(feature = "vouchers");
+pub const PERMIT_ENABLED: bool = cfg!(feature = "permit");
+pub const PERMIT2_ENABLED: bool = cfg!(feature = "permit2");
+
+/// `PriceSource::kind` values.
+pub const PRICE_SOURCE_ORACLE: u8 = 0;
+pub const PRICE_SOURCE_CHAINLINK: u8 = 1;
+
+/// Uniswap's Permit2, deployed at the same address on every chain.
+pub const PERMIT2: Address = address!("000000000022D473030F116dDEE9F6B43aC78BA3");
+
+/// Seconds to wait after the sequencer comes back up before trusting
+/// Chainlink answers again.
+pub const SEQUENCER_GRACE_PERIOD: u64 = 3600;
+
+/// Fixed-point scale of USD amounts and curve parameters (1e18).
+pub const WAD: U256 = U256::from_limbs([1_000_000_000_000_000_000, 0, 0, 0]);
+
+/// `pricing_mode` values. The default, tiered pricing, follows the schedule
+/// set through `set_price_tiers`; the curve modes price every unit sold along
+/// `initial_price` and `curve_rate`.
+pub const PRICING_TIERED: u8 = 0;
+pub const PRICING_LINEAR: u8 = 1;
+pub const PRICING_EXPONENTIAL: u8 = 2;
+/// Dutch auction, set through `configure_dutch_auction`.
+pub const PRICING_DUTCH: u8 = 3;
+
+/// `sale_mode` values besides the default `0`, buying at the current price.
+/// In a batch auction purchases are bids settled at one clearing price; in an
+/// overflow sale contributions past the hard cap are accepted and the supply
+/// is shared out pro rata.
+pub const SALE_MODE_BATCH: u8 = 1;
+pub const SALE_MODE_OVERFLOW: u8 = 2;
+
+/// Smallest batch auction bid, in 18-decimal USD. Every bid is settled in its
+/// own step, so dust bids would only drag settlement out.
+pub const MIN_BID_USD: U256 = WAD;
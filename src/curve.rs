@@ -0,0 +1,330 @@
+//! Closed-form bonding curves. Prices are 18-decimal USD per whole sale
+//! token, `unit` is one whole sale token in its smallest denomination and
+//! `rate` is 18-decimal:
+//!
+//! - linear: `price(s) = initial_price + rate * s`
+//! - exponential: `price(s) = initial_price * e^(rate * s)`
+//!
+//! with `s` counted in whole tokens sold. Every curve function takes the
+//! price at the current `tokens_sold` and rounds in the contract's favour.
+//!
+//! Tiered pricing is a step function over `(threshold, price)` pairs with
+//! increasing thresholds; a purchase crossing thresholds pays each slice at
+//! its own tier's price.
+
+use stylus_sdk::alloy_primitives::U256;
+
+use crate::{constants::WAD, errors::TokenSaleErrors, math};
+
+pub fn linear_price(
+    initial_price: U256,
+    rate: U256,
+    sold: U256,
+    unit: U256,
+) -> Result<U256, TokenSaleErrors> {
+    math::add(initial_price, math::mul_div(rate, sold, unit)?)
+}
+
+/// Tokens bought with `usd`, solving `usd = x * price + rate * x^2 / 2` for
+/// `x` in the cancellation-free form `x = 2 * usd / (price + sqrt(price^2 +
+/// 2 * rate * usd))`.
+pub fn linear_amount_out(
+    price: U256,
+    rate: U256,
+    usd: U256,
+    unit: U256,
+) -> Result<U256, TokenSaleErrors> {
+    let discriminant = math::add(
+        math::mul(price, price)?,
+        math::mul(math::mul(U256::from(2), rate)?, usd)?,
+    )?;
+    let denominator = math::add(price, math::sqrt_up(discriminant))?;
+
+    math::mul_div(math::mul(U256::from(2), usd)?, unit, denominator)
+}
+
+/// USD cost of `amount_out`: `x * price + rate * x^2 / 2`.
+pub fn linear_cost(
+    price: U256,
+    rate: U256,
+    amount_out: U256,
+    unit: U256,
+) -> Result<U256, TokenSaleErrors> {
+    let numerator = math::add(
+        math::mul(math::mul(U256::from(2), price)?, unit)?,
+        math::mul(rate, amount_out)?,
+    )?;
+    let denominator = math::mul(math::mul(U256::from(2), unit)?, unit)?;
+
+    math::mul_div_up(amount_out, numerator, denominator)
+}
+
+pub fn exponential_price(
+    initial_price: U256,
+    rate: U256,
+    sold: U256,
+    unit: U256,
+) -> Result<U256, TokenSaleErrors> {
+    let growth = math::exp_wad(math::mul_div(rate, sold, unit)?)?;
+
+    math::mul_div(initial_price, growth, WAD)
+}
+
+/// Tokens bought with `usd`, solving `usd = price * (e^(rate * x) - 1) /
+/// rate` for `x = ln(1 + usd * rate / price) / rate`.
+pub fn exponential_amount_out(
+    price: U256,
+    rate: U256,
+    usd: U256,
+    unit: U256,
+) -> Result<U256, TokenSaleErrors> {
+    let growth = math::add(WAD, math::mul_div(usd, rate, price)?)?;
+
+    math::mul_div(math::ln_wad(growth)?, unit, rate)
+}
+
+/// USD cost of `amount_out`: `price * (e^(rate * x) - 1) / rate`, with
+/// `e^(rate * x)` rounded up as well.
+pub fn exponential_cost(
+    price: U256,
+    rate: U256,
+    amount_out: U256,
+    unit: U256,
+) -> Result<U256, TokenSaleErrors> {
+    let growth = math::exp_wad_up(math::mul_div_up(rate, amount_out, unit)?)?;
+
+    math::mul_div_up(price, math::sub(growth, WAD)?, rate)
+}
+
+/// Price of the tier `sold` falls into: the price of the highest threshold
+/// already reached, or `initial_price` below the first one.
+pub fn tiered_price(initial_price: U256, tiers: &[(U256, U256)], sold: U256) -> U256 {
+    tiers
+        .iter()
+        .rev()
+        .find(|(threshold, _)| sold >= *threshold)
+        .map_or(initial_price, |(_, price)| *price)
+}
+
+/// First threshold above `sold`, where the next tier starts.
+fn next_threshold(tiers: &[(U256, U256)], sold: U256) -> Option<U256> {
+    tiers
+        .iter()
+        .map(|(threshold, _)| *threshold)
+        .find(|threshold| *threshold > sold)
+}
+
+/// Tokens bought with `usd` from `sold` on. Every tier the purchase reaches
+/// into is paid in full at its own price before the next one is entered.
+pub fn tiered_amount_out(
+    initial_price: U256,
+    tiers: &[(U256, U256)],
+    sold: U256,
+    usd: U256,
+    unit: U256,
+) -> Result<U256, TokenSaleErrors> {
+    let mut sold = sold;
+    let mut usd = usd;
+    let mut amount_out = U256::ZERO;
+
+    loop {
+        let price = tiered_price(initial_price, tiers, sold);
+
+        if let Some(threshold) = next_threshold(tiers, sold) {
+            let slice = threshold - sold;
+            let cost = math::mul_div_up(slice, price, unit)?;
+            if usd >= cost {
+                usd -= cost;
+                amount_out = math::add(amount_out, slice)?;
+                sold = threshold;
+                continue;
+            }
+        }
+
+        return math::add(amount_out, math::mul_div(usd, unit, price)?);
+    }
+}
+
+/// USD cost of `amount_out` from `sold` on, the inverse of
+/// `tiered_amount_out`: each slice is rounded up at its tier's price.
+pub fn tiered_cost(
+    initial_price: U256,
+    tiers: &[(U256, U256)],
+    sold: U256,
+    amount_out: U256,
+    unit: U256,
+) -> Result<U256, TokenSaleErrors> {
+    let mut sold = sold;
+    let mut remaining = amount_out;
+    let mut cost = U256::ZERO;
+
+    while !remaining.is_zero() {
+        let price = tiered_price(initial_price, tiers, sold);
+        let slice = next_threshold(tiers, sold)
+            .map_or(remaining, |threshold| remaining.min(threshold - sold));
+
+        cost = math::add(cost, math::mul_div_up(slice, price, unit)?)?;
+        remaining -= slice;
+        sold = math::add(sold, slice)?;
+    }
+
+    Ok(cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wad(x: u64) -> U256 {
+        U256::from(x) * WAD
+    }
+
+    fn cents(x: u64) -> U256 {
+        U256::from(x) * WAD / U256::from(100)
+    }
+
+    fn usd_amounts() -> impl Iterator<Item = U256> {
+        [
+            1u64,
+            999,
+            1_000_000,
+            123_456_789_012_345_678,
+            5 * 10u64.pow(18),
+        ]
+        .into_iter()
+        .map(U256::from)
+        .chain([wad(10_000), wad(7_777_777)])
+    }
+
+    #[test]
+    fn linear_amount_out_never_overspends() {
+        let (price, rate) = (WAD, cents(1));
+
+        for usd in usd_amounts() {
+            let amount_out = linear_amount_out(price, rate, usd, WAD).ok().unwrap();
+            assert!(linear_cost(price, rate, amount_out, WAD).ok().unwrap() <= usd);
+
+            let cost = linear_cost(price, rate, amount_out, WAD).ok().unwrap();
+            assert!(linear_amount_out(price, rate, cost, WAD).ok().unwrap() <= amount_out);
+        }
+    }
+
+    #[test]
+    fn linear_cost_rounds_up() {
+        // 10 tokens from $1 rising $0.01 per token: 10 + 0.01 * 100 / 2.
+        assert_eq!(
+            linear_cost(WAD, cents(1), wad(10), WAD).ok(),
+            Some(cents(1050))
+        );
+
+        // One wei of token costs one wei of USD plus the rounded-up slope.
+        assert_eq!(
+            linear_cost(WAD, cents(1), U256::from(1), WAD).ok(),
+            Some(U256::from(2))
+        );
+        assert_eq!(
+            linear_cost(WAD, cents(1), U256::ZERO, WAD).ok(),
+            Some(U256::ZERO)
+        );
+    }
+
+    #[test]
+    fn exponential_amount_out_never_overspends() {
+        let (price, rate) = (WAD, cents(1));
+
+        for usd in usd_amounts() {
+            let amount_out = exponential_amount_out(price, rate, usd, WAD).ok().unwrap();
+            assert!(exponential_cost(price, rate, amount_out, WAD).ok().unwrap() <= usd);
+        }
+    }
+
+    #[test]
+    fn exponential_cost_rounds_up() {
+        // (e^0.07 - 1) / 0.01 = 7.250818125421647905310...
+        let cost = exponential_cost(WAD, cents(1), wad(7), WAD).ok().unwrap();
+        let exact = U256::from(7_250_818_125_421_647_905u64);
+        assert!(cost > exact && cost - exact < U256::from(1000));
+
+        // (e - 1) / 0.01 = 171.828182845904523536...
+        let cost = exponential_cost(WAD, cents(1), wad(100), WAD).ok().unwrap();
+        let exact = U256::from(171_828_182_845_904_523_536u128);
+        assert!(cost > exact && cost - exact < U256::from(1000));
+
+        assert_eq!(
+            exponential_cost(WAD, cents(1), U256::ZERO, WAD).ok(),
+            Some(U256::ZERO)
+        );
+    }
+
+    #[test]
+    fn exponential_price_reports_overflow() {
+        // $1 * e^94 still fits in the intermediate product, $1 * e^95 does not.
+        assert!(exponential_price(WAD, WAD, wad(94), WAD).is_ok());
+        assert!(matches!(
+            exponential_price(WAD, WAD, wad(95), WAD),
+            Err(TokenSaleErrors::MathOverflow(_))
+        ));
+    }
+
+    // $1 below 10 tokens, $2 from 10 and $3 from 100.
+    fn tiers() -> [(U256, U256); 2] {
+        [(wad(10), wad(2)), (wad(100), wad(3))]
+    }
+
+    #[test]
+    fn tiered_price_steps_at_thresholds() {
+        assert_eq!(tiered_price(WAD, &tiers(), U256::ZERO), WAD);
+        assert_eq!(tiered_price(WAD, &tiers(), wad(10) - U256::from(1)), WAD);
+        assert_eq!(tiered_price(WAD, &tiers(), wad(10)), wad(2));
+        assert_eq!(tiered_price(WAD, &tiers(), wad(1_000)), wad(3));
+        assert_eq!(tiered_price(WAD, &[], wad(1_000)), WAD);
+    }
+
+    #[test]
+    fn tiered_purchases_pay_each_slice_at_its_tier() {
+        // 0.1 token at $1 and 9.85 at $2.
+        let sold = cents(990);
+        assert_eq!(
+            tiered_amount_out(WAD, &tiers(), sold, cents(1980), WAD).ok(),
+            Some(cents(995))
+        );
+        assert_eq!(
+            tiered_cost(WAD, &tiers(), sold, cents(995), WAD).ok(),
+            Some(cents(1980))
+        );
+
+        // 10 tokens at $1, 90 at $2 and 270 at $3.
+        assert_eq!(
+            tiered_amount_out(WAD, &tiers(), U256::ZERO, wad(1_000), WAD).ok(),
+            Some(wad(370))
+        );
+        assert_eq!(
+            tiered_cost(WAD, &tiers(), U256::ZERO, wad(370), WAD).ok(),
+            Some(wad(1_000))
+        );
+        assert_eq!(
+            tiered_cost(WAD, &tiers(), U256::ZERO, cents(1010), WAD).ok(),
+            Some(cents(1020))
+        );
+    }
+
+    #[test]
+    fn tiered_amount_out_inverts_tiered_cost() {
+        for sold in [U256::ZERO, cents(990), wad(10), wad(99), wad(500)] {
+            for usd in usd_amounts() {
+                let amount_out = tiered_amount_out(WAD, &tiers(), sold, usd, WAD)
+                    .ok()
+                    .unwrap();
+                let cost = tiered_cost(WAD, &tiers(), sold, amount_out, WAD)
+                    .ok()
+                    .unwrap();
+                assert!(cost <= usd);
+
+                let again = tiered_amount_out(WAD, &tiers(), sold, cost, WAD)
+                    .ok()
+                    .unwrap();
+                assert_eq!(again, amount_out);
+            }
+        }
+    }
+}
@@ -5,6 +5,54 @@ sol! {
    error NotAdmin();
    error SaleEnded();
    error EndtimeInPast();
+   error SaleAlreadyStarted();
+   error NothingToClaim();
+   error SaleNotEnded();
+   error SoftCapReached();
+   error SoftCapNotReached();
+   error NothingToRefund();
+   error SoldOut();
+   error UnsupportedToken();
+   error LengthMismatch();
+   error StalePrice();
+   error InvalidPrice();
+   error SequencerDown();
+   error GracePeriodNotOver();
+   error MathOverflow();
+   error DivisionByZero();
+   error TransferFailed(address token);
+   error TransferFromFailed(address token);
+   error OracleCallFailed(address oracle);
+   error DecimalsCallFailed(address token);
+   error InvalidPriceTiers();
+   error InvalidPricingMode();
+   error InvalidAuctionConfig();
+   error InvalidSaleMode();
+   error BidTooLow();
+   error NotSettled();
+   error AlreadySettled();
+   error SaleNotStarted();
+   error PhaseClosed();
+   error InvalidSchedule();
+   error WalletCapExceeded();
+   error WhitelistOnly();
+   error InvalidProof();
+   error AllocationExceeded();
+   error VoucherExpired();
+   error VoucherUsed();
+   error InvalidSignature();
+   error InvalidContributionLimits();
+   error BelowMinimum();
+   error AboveMaximum();
+   error PermitFailed(address token);
+   error SlippageExceeded(uint256 amount_out, uint256 min_amount_out);
+   error DeadlineExpired();
+   error ExcessiveAmountIn(uint256 amount_in, uint256 max_amount_in);
+   error ZeroAmount();
+   error VoucherRequired();
+   error ConflictingGates();
+   error InvalidBidHint();
+   error FeatureDisabled();
 }
 
 #[derive(SolidityError)]
@@ -12,4 +60,52 @@ pub enum TokenSaleErrors {
     NotAdmin(NotAdmin),
     SaleEnded(SaleEnded),
     EndtimeInPast(EndtimeInPast),
+    SaleAlreadyStarted(SaleAlreadyStarted),
+    NothingToClaim(NothingToClaim),
+    SaleNotEnded(SaleNotEnded),
+    SoftCapReached(SoftCapReached),
+    SoftCapNotReached(SoftCapNotReached),
+    NothingToRefund(NothingToRefund),
+    SoldOut(SoldOut),
+    UnsupportedToken(UnsupportedToken),
+    LengthMismatch(LengthMismatch),
+    StalePrice(StalePrice),
+    InvalidPrice(InvalidPrice),
+    SequencerDown(SequencerDown),
+    GracePeriodNotOver(GracePeriodNotOver),
+    MathOverflow(MathOverflow),
+    DivisionByZero(DivisionByZero),
+    TransferFailed(TransferFailed),
+    TransferFromFailed(TransferFromFailed),
+    OracleCallFailed(OracleCallFailed),
+    DecimalsCallFailed(DecimalsCallFailed),
+    InvalidPriceTiers(InvalidPriceTiers),
+    InvalidPricingMode(InvalidPricingMode),
+    InvalidAuctionConfig(InvalidAuctionConfig),
+    InvalidSaleMode(InvalidSaleMode),
+    BidTooLow(BidTooLow),
+    NotSettled(NotSettled),
+    AlreadySettled(AlreadySettled),
+    SaleNotStarted(SaleNotStarted),
+    PhaseClosed(PhaseClosed),
+    InvalidSchedule(InvalidSchedule),
+    WalletCapExceeded(WalletCapExceeded),
+    WhitelistOnly(WhitelistOnly),
+    InvalidProof(InvalidProof),
+    AllocationExceeded(AllocationExceeded),
+    VoucherExpired(VoucherExpired),
+    VoucherUsed(VoucherUsed),
+    InvalidSignature(InvalidSignature),
+    InvalidContributionLimits(InvalidContributionLimits),
+    BelowMinimum(BelowMinimum),
+    AboveMaximum(AboveMaximum),
+    PermitFailed(PermitFailed),
+    SlippageExceeded(SlippageExceeded),
+    DeadlineExpired(DeadlineExpired),
+    ExcessiveAmountIn(ExcessiveAmountIn),
+    ZeroAmount(ZeroAmount),
+    VoucherRequired(VoucherRequired),
+    ConflictingGates(ConflictingGates),
+    InvalidBidHint(InvalidBidHint),
+    FeatureDisabled(FeatureDisabled),
 }
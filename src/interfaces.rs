@@ -1,15 +1,60 @@
+use alloy_sol_types::sol;
 use stylus_sdk::prelude::sol_interface;
 
 sol_interface! {
   interface IERC20 {
     function transfer(address to, uint256 value) external returns (bool);
+    function balanceOf(address account) external view returns (uint256);
     function allowance(address owner, address spender) external view returns (uint256);
     function transferFrom(address from, address to, uint256 value) external returns (bool);
+    function decimals() external view returns (uint8);
   }
 }
 
+// Raw ERC-20 calls, so that tokens returning no data can be handled.
+sol! {
+    function transfer(address to, uint256 value) external returns (bool);
+    function transferFrom(address from, address to, uint256 value) external returns (bool);
+}
+
+sol_interface! {
+  interface IERC20Permit {
+    function permit(address owner, address spender, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s) external;
+  }
+}
+
+// Permit2's signature transfer, called raw since it takes structs.
+sol! {
+    interface IPermit2 {
+        struct TokenPermissions {
+            address token;
+            uint256 amount;
+        }
+
+        struct PermitTransferFrom {
+            TokenPermissions permitted;
+            uint256 nonce;
+            uint256 deadline;
+        }
+
+        struct SignatureTransferDetails {
+            address to;
+            uint256 requestedAmount;
+        }
+
+        function permitTransferFrom(PermitTransferFrom memory permit, SignatureTransferDetails calldata transferDetails, address owner, bytes calldata signature) external;
+    }
+}
+
 sol_interface! {
   interface IOracle {
     function getPrice(uint8 index) external pure returns (uint256) {}
   }
 }
+
+sol_interface! {
+  interface IAggregatorV3 {
+    function decimals() external view returns (uint8);
+    function latestRoundData() external view returns (uint80, int256, uint256, uint256, uint80);
+  }
+}
@@ -1,20 +1,95 @@
-#![cfg_attr(not(feature = "export-abi"), no_main, no_std)]
+#![cfg_attr(not(any(test, feature = "export-abi")), no_main, no_std)]
 extern crate alloc;
 
+// Links the host function shims the storage types call into in unit tests.
+#[cfg(test)]
+extern crate motsu;
+
+mod auction;
 mod constants;
+mod curve;
 mod errors;
 mod interfaces;
+mod math;
+mod merkle;
+mod voucher;
 
 use alloc::vec::Vec;
-use errors::{EndtimeInPast, NotAdmin, SaleEnded, TokenSaleErrors};
-use interfaces::{IOracle, IERC20};
+use alloy_sol_types::{sol, SolCall, SolValue};
+use constants::{
+    ALLOWLIST_ENABLED, BATCH_AUCTION_ENABLED, BONDING_CURVES_ENABLED, CHAINLINK_ENABLED,
+    DUTCH_AUCTION_ENABLED, MIN_BID_USD, OVERFLOW_SALE_ENABLED, PERMIT2, PERMIT2_ENABLED,
+    PERMIT_ENABLED, PRICE_SOURCE_CHAINLINK, PRICE_SOURCE_ORACLE, PRICING_DUTCH,
+    PRICING_EXPONENTIAL, PRICING_LINEAR, PRICING_TIERED, SALE_MODE_BATCH, SALE_MODE_OVERFLOW,
+    SEQUENCER_GRACE_PERIOD, VOUCHERS_ENABLED, WAD,
+};
+use errors::{
+    AboveMaximum, AllocationExceeded, AlreadySettled, BelowMinimum, BidTooLow, ConflictingGates,
+    DeadlineExpired, DecimalsCallFailed, EndtimeInPast, ExcessiveAmountIn, FeatureDisabled,
+    GracePeriodNotOver, InvalidAuctionConfig, InvalidBidHint, InvalidContributionLimits,
+    InvalidPrice, InvalidPriceTiers, InvalidPricingMode, InvalidProof, InvalidSaleMode,
+    InvalidSchedule, InvalidSignature, LengthMismatch, NotAdmin, NotSettled, NothingToClaim,
+    NothingToRefund, OracleCallFailed, PermitFailed, PhaseClosed, SaleAlreadyStarted, SaleEnded,
+    SaleNotEnded, SaleNotStarted, SequencerDown, SlippageExceeded, SoftCapNotReached,
+    SoftCapReached, SoldOut, StalePrice, TokenSaleErrors, TransferFailed, TransferFromFailed,
+    UnsupportedToken, VoucherExpired, VoucherRequired, VoucherUsed, WalletCapExceeded,
+    WhitelistOnly, ZeroAmount,
+};
+use interfaces::{
+    transferCall, transferFromCall, IAggregatorV3, IERC20Permit, IOracle, IPermit2, IERC20,
+};
 use stylus_sdk::{
-    alloy_primitives::{Address, U256},
-    block, console, contract, msg,
+    abi::Bytes,
+    alloy_primitives::{Address, B256, I256, U256, U8},
+    block,
+    call::{self, Call},
+    console, contract, crypto, msg,
     prelude::*,
-    storage::{StorageAddress, StorageBool, StorageMap, StorageU256},
+    storage::{
+        StorageAddress, StorageB256, StorageBool, StorageMap, StorageU256, StorageU8, StorageVec,
+    },
+    types::AddressVM,
 };
 
+/// Where the USD price of a payment token is read from. `kind` selects
+/// between an `IOracle` (using `index`) and a Chainlink feed (using
+/// `heartbeat` as the maximum accepted age of an answer).
+#[storage]
+pub struct PriceSource {
+    kind: StorageU8,
+    oracle: StorageAddress,
+    index: StorageU8,
+    heartbeat: StorageU256,
+}
+
+/// A batch auction bid. `usd_value` is fixed at the oracle price when the
+/// bid is placed.
+#[storage]
+pub struct Bid {
+    bidder: StorageAddress,
+    token: StorageAddress,
+    amount: StorageU256,
+    usd_value: StorageU256,
+    max_price: StorageU256,
+}
+
+sol! {
+    /// Everything the sale page shows, returned by `sale_state()`.
+    #[derive(AbiType)]
+    struct SaleState {
+        address admin;
+        address token;
+        address oracle;
+        uint256 totalSupply;
+        uint256 tokensSold;
+        uint256 raisedUsd;
+        uint256 softCap;
+        uint256 saleStart;
+        uint256 saleEnd;
+        uint256 currentPrice;
+    }
+}
+
 #[storage]
 #[entrypoint]
 pub struct TokenSale {
@@ -27,10 +102,67 @@ pub struct TokenSale {
     sale_end: StorageU256,
     current_price_usd: StorageU256,
     collected_amount: StorageMap<Address, StorageU256>,
+    vesting_enabled: StorageBool,
+    vesting_cliff: StorageU256,
+    vesting_duration: StorageU256,
+    allocations: StorageMap<Address, StorageU256>,
+    claimed: StorageMap<Address, StorageU256>,
+    soft_cap: StorageU256,
+    raised_usd: StorageU256,
+    contributions: StorageMap<Address, StorageMap<Address, StorageU256>>,
+    sale_tokens_reclaimed: StorageBool,
+    supported_tokens: StorageMap<Address, StorageBool>,
+    supported_token_list: StorageVec<StorageAddress>,
+    price_sources: StorageMap<Address, PriceSource>,
+    sequencer_uptime_feed: StorageAddress,
+    token_decimals: StorageU8,
+    payment_decimals: StorageMap<Address, StorageU8>,
+    tier_thresholds: StorageVec<StorageU256>,
+    tier_prices: StorageVec<StorageU256>,
+    pricing_mode: StorageU8,
+    curve_rate: StorageU256,
+    auction_start_price: StorageU256,
+    auction_floor_price: StorageU256,
+    auction_start: StorageU256,
+    sale_mode: StorageU8,
+    bids: StorageVec<Bid>,
+    settled: StorageBool,
+    clearing_price: StorageU256,
+    excess: StorageMap<Address, StorageMap<Address, StorageU256>>,
+    contributed_usd: StorageMap<Address, StorageU256>,
+    overflow_total_usd: StorageU256,
+    overflow_excess_usd: StorageU256,
+    sale_start: StorageU256,
+    phase_starts: StorageVec<StorageU256>,
+    phase_ends: StorageVec<StorageU256>,
+    phase_prices: StorageVec<StorageU256>,
+    phase_wallet_caps: StorageVec<StorageU256>,
+    phase_purchased: StorageMap<Address, StorageMap<U256, StorageU256>>,
+    merkle_root: StorageB256,
+    whitelist_used: StorageMap<Address, StorageU256>,
+    voucher_signer: StorageAddress,
+    used_nonces: StorageMap<Address, StorageMap<U256, StorageBool>>,
+    min_contribution_usd: StorageU256,
+    max_contribution_usd: StorageU256,
+    wallet_usd: StorageMap<Address, StorageU256>,
+    permit2: StorageAddress,
+    accepted_tokens: StorageMap<Address, StorageBool>,
+    accepted_token_list: StorageVec<StorageAddress>,
+    bid_level_head: StorageU256,
+    bid_level_next: StorageMap<U256, StorageU256>,
+    bid_level_usd: StorageMap<U256, StorageU256>,
+    clearing_level: StorageU256,
+    clearing_higher_usd: StorageU256,
+    clearing_fill: StorageU256,
+    auction_cleared: StorageBool,
+    settled_bids: StorageU256,
 }
 
 #[public]
 impl TokenSale {
+    /// `pricing_mode` selects tiered pricing or a bonding curve starting at
+    /// `initial_price` and growing with `curve_rate`, see `curve`.
+    #[allow(clippy::too_many_arguments)]
     pub fn initialise(
         &mut self,
         admin: Address,
@@ -39,7 +171,11 @@ impl TokenSale {
         total_supply: U256,
         sale_end: U256,
         initial_price: U256,
+        soft_cap: U256,
+        pricing_mode: u8,
+        curve_rate: U256,
         supported_tokens: Vec<Address>,
+        price_indices: Vec<u8>,
     ) -> Result<(), TokenSaleErrors> {
         // NOTICE: this check here for the owner is to protect the SC
         // from front-run. Even though stylus technically supports Solidity
@@ -58,198 +194,2044 @@ impl TokenSale {
         //     ));
         // }
 
-        // NOTICE: commented out due to "max code size exceeded" reason
-        // Also, Address Zero notifies Native Gas Currency which can be used
-        // to buy tokens. Commented out as I will not be able to implement that
-        // due to the contract size issue.
-
-        // self.collected_amount.insert(Address::ZERO, U256::ZERO);
-
         if sale_end < U256::from(block::timestamp()) {
             return Err(TokenSaleErrors::EndtimeInPast(EndtimeInPast {}));
         }
 
-        if supported_tokens.len() > 0 {
-            for s_token in supported_tokens {
-                self.collected_amount.insert(s_token, U256::ZERO);
-            }
+        if supported_tokens.len() != price_indices.len() {
+            return Err(TokenSaleErrors::LengthMismatch(LengthMismatch {}));
         }
 
-        // NOTICE: commented out due to "max code size exceeded" reason
-
-        // let allowance = token.allowance(&*self, admin, contract::address()).unwrap();
-        // if allowance < total_supply {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
-
-        self.transfer_token_from(token, total_supply, admin, contract::address());
+        if pricing_mode > PRICING_EXPONENTIAL
+            || (pricing_mode != PRICING_TIERED && !BONDING_CURVES_ENABLED)
+            || (pricing_mode == PRICING_EXPONENTIAL && curve_rate.is_zero())
+        {
+            return Err(TokenSaleErrors::InvalidPricingMode(InvalidPricingMode {}));
+        }
 
-        // NOTICE: commented out due to "max code size exceeded" reason
+        for (s_token, price_index) in supported_tokens.into_iter().zip(price_indices) {
+            self.add_token(s_token, oracle, price_index)?;
+        }
 
-        // if !ok {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
+        self.transfer_token_from(token, total_supply, admin, contract::address())?;
 
         self.is_initialised.set(true);
         self.admin.set(admin);
         self.token.set(token);
+        self.token_decimals
+            .set(U8::from(self.read_decimals(token)?));
         self.tokens_sold.set(U256::ZERO);
         self.oracle.set(oracle);
         self.total_supply.set(total_supply);
         self.sale_end.set(sale_end);
         self.current_price_usd.set(initial_price);
+        self.soft_cap.set(soft_cap);
+        self.pricing_mode.set(U8::from(pricing_mode));
+        self.curve_rate.set(curve_rate);
 
         Ok(())
     }
 
+    /// In a batch auction this places a bid without a price limit. Closed
+    /// while an allowlist is set, see `buy_token_whitelisted`. Purchases are
+    /// priced on what arrives, so fee-on-transfer tokens pay for less.
     pub fn buy_token(
         &mut self,
         amount: U256,
         token_in: Address,
-        price_index: u8,
+        allow_partial: bool,
     ) -> Result<(), TokenSaleErrors> {
-        if self.sale_end.get() < U256::from(block::timestamp()) {
-            return Err(TokenSaleErrors::SaleEnded(SaleEnded {}));
+        self.check_public()?;
+
+        if self.is_batch_auction() {
+            return self.place_bid(amount, token_in, U256::MAX, U256::ZERO);
         }
-        // NOTICE: commented out due to "max code size exceeded" reason
 
-        // if amount.is_zero() {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
+        let received = self.pull_funds(token_in, amount)?;
 
-        // NOTICE: commented out due to "max code size exceeded" reason
+        if self.is_overflow_sale() {
+            return self.record_contribution(received, token_in);
+        }
 
-        // if !self.supported_tokens.get(token_in.address) {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
+        self.complete_purchase(received, token_in, allow_partial)?;
 
-        // NOTICE: commented out due to "max code size exceeded" reason
+        Ok(())
+    }
 
-        // if self.total_supply.get() < self.tokens_sold.get() + amount {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
+    /// Buys like `buy_token` but only if it gets at least `min_amount_out`
+    /// sale tokens and lands no later than `deadline`, since the price moves
+    /// with every purchase and with the oracle.
+    pub fn buy_token_exact(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        min_amount_out: U256,
+        deadline: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        if U256::from(block::timestamp()) > deadline {
+            return Err(TokenSaleErrors::DeadlineExpired(DeadlineExpired {}));
+        }
 
-        // NOTICE: commented out due to "max code size exceeded" reason
+        self.check_public()?;
 
-        // let allowance = token_in
-        //     .allowance(&*self, msg::sender(), contract::address())
-        //     .unwrap();
-        // if allowance < amount {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
-        let amount_out = self.calculate_amount_out(amount, price_index);
+        if !self.sale_mode.get().is_zero() {
+            return Err(TokenSaleErrors::InvalidSaleMode(InvalidSaleMode {}));
+        }
 
-        self.tokens_sold.set(self.tokens_sold.get() + amount_out);
+        let received = self.pull_funds(token_in, amount)?;
+        let (_, amount_out) = self.record_purchase(received, token_in, false)?;
+        if amount_out < min_amount_out {
+            return Err(TokenSaleErrors::SlippageExceeded(SlippageExceeded {
+                amount_out,
+                min_amount_out,
+            }));
+        }
 
-        self.current_price_usd.set(self.calculate_price());
+        self.deliver(amount_out)?;
 
-        self.transfer_token_from(token_in, amount, msg::sender(), contract::address());
+        Ok(())
+    }
 
-        // NOTICE: commented out due to "max code size exceeded" reason
+    /// Buys exactly `amount_out` sale tokens for whatever `token_in` they
+    /// cost, as long as that is no more than `max_amount_in`. The cost is
+    /// rounded up, so the payment may be worth a few wei more.
+    pub fn buy_exact_tokens(
+        &mut self,
+        amount_out: U256,
+        token_in: Address,
+        max_amount_in: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        self.check_public()?;
 
-        // if !ok {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
+        if !self.sale_mode.get().is_zero() {
+            return Err(TokenSaleErrors::InvalidSaleMode(InvalidSaleMode {}));
+        }
+
+        self.check_open(amount_out, token_in)?;
+
+        let amount_in = self.amount_in_for_tokens(amount_out, token_in)?;
+        if amount_in > max_amount_in {
+            return Err(TokenSaleErrors::ExcessiveAmountIn(ExcessiveAmountIn {
+                amount_in,
+                max_amount_in,
+            }));
+        }
 
-        self.transfer_token(self.token.get(), amount_out, msg::sender());
+        let received = self.pull_funds(token_in, amount_in)?;
+        let usd_value = self.usd_value(received, token_in)?;
 
-        self.set_collected_amount(token_in, self.collected_amount.get(token_in) + amount);
+        // Fee-on-transfer tokens deliver less than `amount_in`.
+        if usd_value < self.usd_for_tokens(amount_out)? {
+            return Err(TokenSaleErrors::SlippageExceeded(SlippageExceeded {
+                amount_out: self.tokens_for_usd(usd_value)?,
+                min_amount_out: amount_out,
+            }));
+        }
+
+        if amount_out > math::sub(self.total_supply.get(), self.tokens_sold.get())? {
+            return Err(TokenSaleErrors::SoldOut(SoldOut {}));
+        }
+
+        self.book_purchase(received, token_in, amount_out, usd_value)?;
+        self.deliver(amount_out)?;
 
         Ok(())
     }
 
-    pub fn withdraw(&mut self, token_addr: Address) -> Result<(), TokenSaleErrors> {
-        if msg::sender() != self.admin.get() {
-            return Err(TokenSaleErrors::NotAdmin(NotAdmin {}));
+    /// Buys like `buy_token` after approving the sale with an EIP-2612
+    /// `permit` signed by `msg::sender()`, so no separate approval is needed.
+    /// If the permit was already used, e.g. by someone front-running it, the
+    /// purchase still goes through as long as the allowance is in place.
+    pub fn buy_token_with_permit(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        deadline: U256,
+        v: u8,
+        r: B256,
+        s: B256,
+    ) -> Result<(), TokenSaleErrors> {
+        check_enabled(PERMIT_ENABLED)?;
+
+        let permitted = IERC20Permit::new(token_in)
+            .permit(
+                &mut *self,
+                msg::sender(),
+                contract::address(),
+                amount,
+                deadline,
+                v,
+                r,
+                s,
+            )
+            .is_ok();
+
+        if !permitted {
+            let allowance = IERC20::new(token_in)
+                .allowance(&*self, msg::sender(), contract::address())
+                .unwrap_or_default();
+            if allowance < amount {
+                return Err(TokenSaleErrors::PermitFailed(PermitFailed {
+                    token: token_in,
+                }));
+            }
         }
 
-        self.transfer_token(
-            token_addr,
-            self.collected_amount.get(token_addr),
-            msg::sender(),
+        self.buy_token(amount, token_in, false)
+    }
+
+    /// Buys like `buy_token`, pulling the payment through Permit2 with a
+    /// `PermitTransferFrom` signature from `msg::sender()` for `amount` of
+    /// `token_in`.
+    pub fn buy_token_permit2(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        allow_partial: bool,
+        nonce: U256,
+        deadline: U256,
+        signature: Bytes,
+    ) -> Result<(), TokenSaleErrors> {
+        check_enabled(PERMIT2_ENABLED)?;
+
+        self.check_public()?;
+
+        let before = self.balance_of(token_in)?;
+        self.transfer_token_permit2(token_in, amount, nonce, deadline, signature)?;
+        let received = math::sub(self.balance_of(token_in)?, before)?;
+
+        if self.is_batch_auction() {
+            return self.record_bid(received, token_in, U256::MAX, U256::ZERO);
+        }
+
+        if self.is_overflow_sale() {
+            return self.record_contribution(received, token_in);
+        }
+
+        self.complete_purchase(received, token_in, allow_partial)?;
+
+        Ok(())
+    }
+
+    /// Points `buy_token_permit2` at another Permit2 deployment.
+    /// `Address::ZERO` goes back to the canonical one. Fixed once the sale has
+    /// started, as buyers approve the Permit2 contract rather than the sale.
+    pub fn set_permit2(&mut self, permit2: Address) -> Result<(), TokenSaleErrors> {
+        check_enabled(PERMIT2_ENABLED)?;
+
+        self.only_admin()?;
+
+        self.check_not_started()?;
+
+        self.permit2.set(permit2);
+
+        Ok(())
+    }
+
+    /// Buys with the native gas currency, which is booked under
+    /// `Address::ZERO` alongside the ERC-20 payment tokens.
+    #[payable]
+    pub fn buy_with_native(&mut self) -> Result<(), TokenSaleErrors> {
+        self.check_public()?;
+
+        if self.is_batch_auction() {
+            return self.record_bid(msg::value(), Address::ZERO, U256::MAX, U256::ZERO);
+        }
+
+        if self.is_overflow_sale() {
+            return self.record_contribution(msg::value(), Address::ZERO);
+        }
+
+        let (_, amount_out) = self.record_purchase(msg::value(), Address::ZERO, false)?;
+
+        self.deliver(amount_out)?;
+
+        Ok(())
+    }
+
+    /// Buys like `buy_token` for an allowlisted `msg::sender()`, who may buy
+    /// up to `max_allocation` sale tokens in total. `proof` shows that
+    /// `keccak256(abi.encodePacked(msg.sender, max_allocation))` is a leaf of
+    /// `merkle_root`.
+    pub fn buy_token_whitelisted(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        max_allocation: U256,
+        proof: Vec<B256>,
+    ) -> Result<(), TokenSaleErrors> {
+        check_enabled(ALLOWLIST_ENABLED)?;
+
+        if !self.sale_mode.get().is_zero() {
+            return Err(TokenSaleErrors::InvalidSaleMode(InvalidSaleMode {}));
+        }
+
+        let leaf = crypto::keccak(
+            [
+                msg::sender().as_slice(),
+                &max_allocation.to_be_bytes::<32>(),
+            ]
+            .concat(),
         );
+        if !merkle::verify(&proof, self.merkle_root.get(), leaf) {
+            return Err(TokenSaleErrors::InvalidProof(InvalidProof {}));
+        }
 
-        self.set_collected_amount(token_addr, U256::from(0));
+        let received = self.pull_funds(token_in, amount)?;
+        let (_, amount_out) = self.record_purchase(received, token_in, false)?;
+
+        let used = math::add(self.whitelist_used.get(msg::sender()), amount_out)?;
+        if used > max_allocation {
+            return Err(TokenSaleErrors::AllocationExceeded(AllocationExceeded {}));
+        }
+        self.whitelist_used.insert(msg::sender(), used);
+
+        self.deliver(amount_out)?;
 
         Ok(())
     }
 
-    pub fn is_initialised(&self) -> bool {
-        self.is_initialised.get()
+    /// Restricts purchases to the allowlist committed to by `root`.
+    /// `B256::ZERO` opens the sale to everyone again. The allowlist and
+    /// vouchers are alternative gates, so this fails while a voucher signer is
+    /// set.
+    pub fn set_merkle_root(&mut self, root: B256) -> Result<(), TokenSaleErrors> {
+        check_enabled(ALLOWLIST_ENABLED)?;
+
+        self.only_admin()?;
+
+        if !root.is_zero() && !self.voucher_signer.get().is_zero() {
+            return Err(TokenSaleErrors::ConflictingGates(ConflictingGates {}));
+        }
+
+        self.merkle_root.set(root);
+
+        Ok(())
+    }
+
+    pub fn whitelist_used(&self, account: Address) -> U256 {
+        self.whitelist_used.get(account)
     }
 
-    pub fn calculate_amount_out(&self, amount: U256, price_index: u8) -> U256 {
-        let price = self.get_price(price_index);
+    /// Buys like `buy_token` with a voucher from `voucher_signer` that lets
+    /// `msg::sender()` buy up to `max_amount` sale tokens until `expiry`.
+    /// Each `nonce` can be used once per buyer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn buy_token_with_voucher(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        max_amount: U256,
+        expiry: U256,
+        nonce: U256,
+        v: u8,
+        r: B256,
+        s: B256,
+    ) -> Result<(), TokenSaleErrors> {
+        check_enabled(VOUCHERS_ENABLED)?;
+
+        if !self.sale_mode.get().is_zero() {
+            return Err(TokenSaleErrors::InvalidSaleMode(InvalidSaleMode {}));
+        }
+
+        if expiry < U256::from(block::timestamp()) {
+            return Err(TokenSaleErrors::VoucherExpired(VoucherExpired {}));
+        }
+
+        if self.used_nonces.get(msg::sender()).get(nonce) {
+            return Err(TokenSaleErrors::VoucherUsed(VoucherUsed {}));
+        }
+
+        let digest = voucher::digest(msg::sender(), max_amount, expiry, nonce);
+        let signer = self.voucher_signer.get();
+        if signer.is_zero() || voucher::recover(digest, v, r, s) != Some(signer) {
+            return Err(TokenSaleErrors::InvalidSignature(InvalidSignature {}));
+        }
+
+        self.used_nonces.setter(msg::sender()).insert(nonce, true);
+
+        let received = self.pull_funds(token_in, amount)?;
+        let (_, amount_out) = self.record_purchase(received, token_in, false)?;
+        if amount_out > max_amount {
+            return Err(TokenSaleErrors::AllocationExceeded(AllocationExceeded {}));
+        }
 
-        let current_price = self.current_price_usd.get();
+        self.deliver(amount_out)?;
 
-        (amount * price) / current_price
+        Ok(())
     }
-}
 
-impl TokenSale {
-    fn calculate_price(&self) -> U256 {
-        let increments = self.tokens_sold.get() * U256::from(10) / self.total_supply.get();
+    /// Restricts purchases to `buy_token_with_voucher` with vouchers signed by
+    /// `signer`. `Address::ZERO` opens the sale to everyone again. Fails while
+    /// an allowlist is set, see `set_merkle_root`.
+    pub fn set_voucher_signer(&mut self, signer: Address) -> Result<(), TokenSaleErrors> {
+        check_enabled(VOUCHERS_ENABLED)?;
+
+        self.only_admin()?;
 
-        let new_price = self.current_price_usd.get()
-            + (self.current_price_usd.get() * increments / U256::from(1));
+        if !signer.is_zero() && !self.merkle_root.get().is_zero() {
+            return Err(TokenSaleErrors::ConflictingGates(ConflictingGates {}));
+        }
+
+        self.voucher_signer.set(signer);
+
+        Ok(())
+    }
 
-        new_price
+    /// The EIP-712 digest `voucher_signer` signs to issue a voucher.
+    pub fn voucher_digest(
+        &self,
+        buyer: Address,
+        max_amount: U256,
+        expiry: U256,
+        nonce: U256,
+    ) -> B256 {
+        voucher::digest(buyer, max_amount, expiry, nonce)
     }
 
-    fn get_price(&self, price_index: u8) -> U256 {
-        let oracle = IOracle::new(self.oracle.get());
-        oracle.get_price(&*self, price_index).unwrap()
+    pub fn is_nonce_used(&self, buyer: Address, nonce: U256) -> bool {
+        self.used_nonces.get(buyer).get(nonce)
     }
 
-    fn transfer_token(&mut self, token_addr: Address, amount: U256, to: Address) {
-        let token = IERC20::new(token_addr);
-        let _ = token.transfer(&mut *self, to, amount).unwrap();
+    /// Sets the smallest USD value a single purchase may have and the most a
+    /// wallet may spend over the whole sale, both at 18 decimals. A zero
+    /// `max_usd` removes the maximum.
+    pub fn set_contribution_limits(
+        &mut self,
+        min_usd: U256,
+        max_usd: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
 
-        // NOTICE: commented out due to "max code size exceeded" reason
+        if !max_usd.is_zero() && min_usd > max_usd {
+            return Err(TokenSaleErrors::InvalidContributionLimits(
+                InvalidContributionLimits {},
+            ));
+        }
 
-        // if !ok {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
+        self.min_contribution_usd.set(min_usd);
+        self.max_contribution_usd.set(max_usd);
+
+        Ok(())
     }
 
-    fn transfer_token_from(
+    /// USD value `account` can still spend before reaching the maximum.
+    pub fn remaining_allocation(&self, account: Address) -> U256 {
+        let max_usd = self.max_contribution_usd.get();
+        if max_usd.is_zero() {
+            return U256::MAX;
+        }
+
+        max_usd.saturating_sub(self.wallet_usd.get(account))
+    }
+
+    /// Returns everything `msg::sender()` paid in `token_in` once the sale has
+    /// ended without reaching the soft cap.
+    pub fn refund(&mut self, token_in: Address) -> Result<(), TokenSaleErrors> {
+        if U256::from(block::timestamp()) <= self.sale_end.get() {
+            return Err(TokenSaleErrors::SaleNotEnded(SaleNotEnded {}));
+        }
+
+        self.check_settled()?;
+        self.allocate_overflow(msg::sender())?;
+
+        if self.soft_cap_reached() {
+            return Err(TokenSaleErrors::SoftCapReached(SoftCapReached {}));
+        }
+
+        let amount = self.contributions.get(msg::sender()).get(token_in);
+        if amount.is_zero() {
+            return Err(TokenSaleErrors::NothingToRefund(NothingToRefund {}));
+        }
+
+        self.contributions
+            .setter(msg::sender())
+            .setter(token_in)
+            .set(U256::ZERO);
+
+        // `finalize()` sets aside the excess of an overflow sale rounded for
+        // the whole token, so the refunds can add up to a few wei more than
+        // what is left booked.
+        self.set_collected_amount(
+            token_in,
+            self.collected_amount.get(token_in).saturating_sub(amount),
+        );
+
+        self.send_funds(token_in, amount, msg::sender())?;
+
+        Ok(())
+    }
+
+    /// Sends the sale tokens nobody can claim back to the admin after the sale
+    /// has ended: the unsold remainder, or the whole supply if the soft cap
+    /// was missed.
+    pub fn reclaim_sale_tokens(&mut self) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        if U256::from(block::timestamp()) <= self.sale_end.get() {
+            return Err(TokenSaleErrors::SaleNotEnded(SaleNotEnded {}));
+        }
+
+        self.check_settled()?;
+
+        let amount = if self.soft_cap_reached() {
+            math::sub(self.total_supply.get(), self.tokens_sold.get())?
+        } else {
+            self.total_supply.get()
+        };
+
+        if self.sale_tokens_reclaimed.get() || amount.is_zero() {
+            return Err(TokenSaleErrors::NothingToClaim(NothingToClaim {}));
+        }
+
+        self.sale_tokens_reclaimed.set(true);
+
+        self.transfer_token(self.token.get(), amount, msg::sender())?;
+
+        Ok(())
+    }
+
+    /// Enables vesting for all subsequent purchases. Nothing is released
+    /// before `sale_end + cliff`, after which the allocation unlocks linearly
+    /// over `duration` seconds.
+    pub fn configure_vesting(
         &mut self,
-        token_addr: Address,
-        amount: U256,
-        from: Address,
-        to: Address,
-    ) {
-        let token = IERC20::new(token_addr);
-        let _ = token.transfer_from(&mut *self, from, to, amount).unwrap();
+        cliff: U256,
+        duration: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
 
-        // NOTICE: commented out due to "max code size exceeded" reason
+        self.check_not_started()?;
 
-        // if !ok {
-        //     return Err(TokenSaleErrors::ZeroAddressNotAllowed(
-        //         ZeroAddressNotAllowed {},
-        //     ));
-        // }
+        self.vesting_enabled.set(true);
+        self.vesting_cliff.set(cliff);
+        self.vesting_duration.set(duration);
+
+        Ok(())
     }
 
-    fn set_collected_amount(&mut self, token_addr: Address, new_amount: U256) {
-        let mut amount_setter = self.collected_amount.setter(token_addr);
-        amount_setter.set(new_amount);
+    /// Releases the vested allocation of `msg::sender()`. After a batch
+    /// auction or an overflow sale this also returns the unspent part of their
+    /// payments, which does not depend on the soft cap.
+    pub fn claim(&mut self) -> Result<(), TokenSaleErrors> {
+        self.check_settled()?;
+        self.allocate_overflow(msg::sender())?;
+
+        let refunded = self.pay_excess(msg::sender())?;
+
+        if !self.soft_cap_reached() {
+            if refunded {
+                return Ok(());
+            }
+            return Err(TokenSaleErrors::SoftCapNotReached(SoftCapNotReached {}));
+        }
+
+        let amount = self.claimable_of(msg::sender())?;
+        if amount.is_zero() {
+            if refunded {
+                return Ok(());
+            }
+            return Err(TokenSaleErrors::NothingToClaim(NothingToClaim {}));
+        }
+
+        let claimed = math::add(self.claimed.get(msg::sender()), amount)?;
+        self.claimed.setter(msg::sender()).set(claimed);
+
+        self.transfer_token(self.token.get(), amount, msg::sender())?;
+
+        Ok(())
+    }
+
+    /// Returns the unspent part of what `msg::sender()` paid in `token_in`
+    /// once an overflow sale has been finalized.
+    pub fn refund_excess(&mut self, token_in: Address) -> Result<(), TokenSaleErrors> {
+        self.check_settled()?;
+        self.allocate_overflow(msg::sender())?;
+
+        let amount = self.excess.get(msg::sender()).get(token_in);
+        if amount.is_zero() {
+            return Err(TokenSaleErrors::NothingToRefund(NothingToRefund {}));
+        }
+
+        self.excess
+            .setter(msg::sender())
+            .setter(token_in)
+            .set(U256::ZERO);
+
+        self.send_funds(token_in, amount, msg::sender())?;
+
+        Ok(())
     }
+
+    pub fn withdraw(&mut self, token_addr: Address) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        self.check_settled()?;
+
+        if !self.soft_cap_reached() {
+            return Err(TokenSaleErrors::SoftCapNotReached(SoftCapNotReached {}));
+        }
+
+        self.send_funds(
+            token_addr,
+            self.collected_amount.get(token_addr),
+            msg::sender(),
+        )?;
+
+        self.set_collected_amount(token_addr, U256::from(0));
+
+        Ok(())
+    }
+
+    /// Allows `token` as a payment token priced by `oracle` at `price_index`.
+    /// `Address::ZERO` enables purchases with the native gas currency.
+    pub fn add_supported_token(
+        &mut self,
+        token: Address,
+        oracle: Address,
+        price_index: u8,
+    ) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        self.add_token(token, oracle, price_index)?;
+
+        Ok(())
+    }
+
+    pub fn set_price_source(
+        &mut self,
+        token: Address,
+        oracle: Address,
+        price_index: u8,
+    ) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        if !self.supported_tokens.get(token) {
+            return Err(TokenSaleErrors::UnsupportedToken(UnsupportedToken {}));
+        }
+
+        self.set_source(token, oracle, price_index);
+
+        Ok(())
+    }
+
+    /// Prices `token` through the Chainlink `feed`, rejecting answers older
+    /// than `heartbeat` seconds.
+    pub fn set_chainlink_price_source(
+        &mut self,
+        token: Address,
+        feed: Address,
+        heartbeat: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        check_enabled(CHAINLINK_ENABLED)?;
+
+        self.only_admin()?;
+
+        if !self.supported_tokens.get(token) {
+            return Err(TokenSaleErrors::UnsupportedToken(UnsupportedToken {}));
+        }
+
+        let mut source = self.price_sources.setter(token);
+        source.kind.set(U8::from(PRICE_SOURCE_CHAINLINK));
+        source.oracle.set(feed);
+        source.index.set(U8::ZERO);
+        source.heartbeat.set(heartbeat);
+
+        Ok(())
+    }
+
+    /// Sets the Arbitrum sequencer uptime feed checked before every Chainlink
+    /// read. `Address::ZERO` disables the check.
+    pub fn set_sequencer_uptime_feed(&mut self, feed: Address) -> Result<(), TokenSaleErrors> {
+        check_enabled(CHAINLINK_ENABLED)?;
+
+        self.only_admin()?;
+
+        self.sequencer_uptime_feed.set(feed);
+
+        Ok(())
+    }
+
+    pub fn price_source(&self, token: Address) -> (Address, u8) {
+        let source = self.price_sources.get(token);
+        (source.oracle.get(), source.index.get().to::<u8>())
+    }
+
+    /// Stops accepting `token` for new purchases. Amounts already collected in
+    /// it stay withdrawable and refundable.
+    pub fn remove_supported_token(&mut self, token: Address) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        if !self.supported_tokens.get(token) {
+            return Err(TokenSaleErrors::UnsupportedToken(UnsupportedToken {}));
+        }
+
+        self.supported_tokens.insert(token, false);
+
+        let last = self.supported_token_list.len() - 1;
+        for i in 0..=last {
+            if self.supported_token_list.get(i) == Some(token) {
+                let last_token = self.supported_token_list.get(last).unwrap();
+                self.supported_token_list.setter(i).unwrap().set(last_token);
+                self.supported_token_list.pop();
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn supported_tokens(&self) -> Vec<Address> {
+        (0..self.supported_token_list.len())
+            .filter_map(|i| self.supported_token_list.get(i))
+            .collect()
+    }
+
+    pub fn is_initialised(&self) -> bool {
+        self.is_initialised.get()
+    }
+
+    pub fn vested_of(&self, account: Address) -> Result<U256, TokenSaleErrors> {
+        let allocation = self.allocations.get(account);
+        let cliff_end = math::add(self.sale_end.get(), self.vesting_cliff.get())?;
+        let now = U256::from(block::timestamp());
+
+        if now < cliff_end {
+            return Ok(U256::ZERO);
+        }
+
+        let elapsed = now - cliff_end;
+        let duration = self.vesting_duration.get();
+
+        if elapsed >= duration {
+            Ok(allocation)
+        } else {
+            math::mul_div(allocation, elapsed, duration)
+        }
+    }
+
+    pub fn claimable_of(&self, account: Address) -> Result<U256, TokenSaleErrors> {
+        math::sub(self.vested_of(account)?, self.claimed.get(account))
+    }
+
+    /// Replaces the price schedule. Tokens sold past `thresholds[i]` are
+    /// priced at `prices[i]`, so a purchase crossing a threshold pays each
+    /// side at its own tier; thresholds must be strictly increasing and
+    /// prices non-zero. Only used by tiered pricing.
+    pub fn set_price_tiers(
+        &mut self,
+        thresholds: Vec<U256>,
+        prices: Vec<U256>,
+    ) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        self.check_not_started()?;
+
+        if thresholds.len() != prices.len() {
+            return Err(TokenSaleErrors::LengthMismatch(LengthMismatch {}));
+        }
+
+        if prices.iter().any(|price| price.is_zero())
+            || thresholds.windows(2).any(|pair| pair[1] <= pair[0])
+        {
+            return Err(TokenSaleErrors::InvalidPriceTiers(InvalidPriceTiers {}));
+        }
+
+        while self.tier_thresholds.pop().is_some() {}
+        while self.tier_prices.pop().is_some() {}
+
+        for (threshold, price) in thresholds.into_iter().zip(prices) {
+            self.tier_thresholds.push(threshold);
+            self.tier_prices.push(price);
+        }
+
+        Ok(())
+    }
+
+    /// Switches to a Dutch auction: the price stays at `start_price` until
+    /// `start_time`, then decays linearly to `floor_price` at `sale_end`.
+    pub fn configure_dutch_auction(
+        &mut self,
+        start_price: U256,
+        floor_price: U256,
+        start_time: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        check_enabled(DUTCH_AUCTION_ENABLED)?;
+
+        self.only_admin()?;
+
+        self.check_not_started()?;
+
+        if floor_price.is_zero() || floor_price > start_price || start_time >= self.sale_end.get() {
+            return Err(TokenSaleErrors::InvalidAuctionConfig(
+                InvalidAuctionConfig {},
+            ));
+        }
+
+        self.pricing_mode.set(U8::from(PRICING_DUTCH));
+        self.auction_start_price.set(start_price);
+        self.auction_floor_price.set(floor_price);
+        self.auction_start.set(start_time);
+
+        Ok(())
+    }
+
+    pub fn current_auction_price(&self) -> Result<U256, TokenSaleErrors> {
+        check_enabled(DUTCH_AUCTION_ENABLED)?;
+
+        self.auction_price()
+    }
+
+    /// Selects how purchases are filled, see `SALE_MODE_BATCH`. In a batch
+    /// auction `initial_price` is the reserve price.
+    pub fn set_sale_mode(&mut self, mode: u8) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        self.check_not_started()?;
+
+        if mode > SALE_MODE_OVERFLOW
+            || (mode == SALE_MODE_BATCH && !BATCH_AUCTION_ENABLED)
+            || (mode == SALE_MODE_OVERFLOW && !OVERFLOW_SALE_ENABLED)
+        {
+            return Err(TokenSaleErrors::InvalidSaleMode(InvalidSaleMode {}));
+        }
+
+        self.sale_mode.set(U8::from(mode));
+
+        Ok(())
+    }
+
+    /// Escrows a batch auction bid of `amount` of `token_in` that accepts any
+    /// clearing price up to `max_price`. `hint` is where the bid's price level
+    /// is looked up from, see `bid_level_hint()`.
+    pub fn place_bid(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        max_price: U256,
+        hint: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        self.check_public()?;
+
+        let received = self.pull_funds(token_in, amount)?;
+
+        self.record_bid(received, token_in, max_price, hint)
+    }
+
+    /// The `hint` to bid at `max_price` with: the lowest bid price level above
+    /// it, or zero if there is none. Stays usable when levels are added before
+    /// the bid lands, at the cost of walking past them.
+    pub fn bid_level_hint(&self, max_price: U256) -> U256 {
+        self.bid_level_position(U256::ZERO, max_price).0
+    }
+
+    /// Clears the batch auction once the sale has ended and books every
+    /// bidder's allocation and unspent payment for `claim()`. Runs in steps of
+    /// one bid price level, then one bid, at most `max_steps` per call and
+    /// resuming where the last call stopped, until `is_settled()`.
+    pub fn settle(&mut self, max_steps: u32) -> Result<(), TokenSaleErrors> {
+        if !self.is_batch_auction() {
+            return Err(TokenSaleErrors::InvalidSaleMode(InvalidSaleMode {}));
+        }
+
+        if U256::from(block::timestamp()) <= self.sale_end.get() {
+            return Err(TokenSaleErrors::SaleNotEnded(SaleNotEnded {}));
+        }
+
+        if self.settled.get() {
+            return Err(TokenSaleErrors::AlreadySettled(AlreadySettled {}));
+        }
+
+        let unit_out = math::pow10(self.token_decimals.get().to::<u8>())?;
+        let mut steps = 0;
+
+        while !self.auction_cleared.get() && steps < max_steps {
+            self.clear_next_level(unit_out)?;
+            steps += 1;
+        }
+
+        let price = self.clearing_price.get();
+        let fill = self.clearing_fill.get();
+        let bids = U256::from(self.bids.len());
+
+        while self.auction_cleared.get() && self.settled_bids.get() < bids && steps < max_steps {
+            let i = self.settled_bids.get();
+            self.settle_bid(i.to::<usize>(), price, fill, unit_out)?;
+            self.settled_bids.set(i + U256::from(1));
+            steps += 1;
+        }
+
+        if self.auction_cleared.get() && self.settled_bids.get() == bids {
+            self.settled.set(true);
+        }
+
+        Ok(())
+    }
+
+    /// Closes an overflow sale once it has ended. If the contributions are
+    /// worth more than the supply at `initial_price`, every contributor gets
+    /// `total_supply * contribution / total_contributed` tokens and the same
+    /// share of each payment back; otherwise every contribution is filled.
+    pub fn finalize(&mut self) -> Result<(), TokenSaleErrors> {
+        if !self.is_overflow_sale() {
+            return Err(TokenSaleErrors::InvalidSaleMode(InvalidSaleMode {}));
+        }
+
+        if U256::from(block::timestamp()) <= self.sale_end.get() {
+            return Err(TokenSaleErrors::SaleNotEnded(SaleNotEnded {}));
+        }
+
+        if self.settled.get() {
+            return Err(TokenSaleErrors::AlreadySettled(AlreadySettled {}));
+        }
+
+        let total_usd = self.raised_usd.get();
+        let price = self.current_price_usd.get();
+        let unit_out = math::pow10(self.token_decimals.get().to::<u8>())?;
+        let supply = self.total_supply.get();
+
+        let demand = math::mul_div(total_usd, unit_out, price)?;
+        let sold_usd = if demand > supply {
+            math::mul_div_up(supply, price, unit_out)?
+        } else {
+            total_usd
+        };
+        let excess_usd = math::sub(total_usd, sold_usd)?;
+
+        // Set aside the unspent share of every payment token up front, so
+        // `withdraw()` cannot touch what contributors are still owed.
+        if !excess_usd.is_zero() {
+            for i in 0..self.accepted_token_list.len() {
+                let token = self.accepted_token_list.get(i).unwrap();
+                let collected = self.collected_amount.get(token);
+                let reserved = math::mul_div(collected, excess_usd, total_usd)?;
+                self.set_collected_amount(token, math::sub(collected, reserved)?);
+            }
+        }
+
+        self.settled.set(true);
+        self.tokens_sold.set(demand.min(supply));
+        self.raised_usd.set(sold_usd);
+        self.overflow_total_usd.set(total_usd);
+        self.overflow_excess_usd.set(excess_usd);
+
+        Ok(())
+    }
+
+    pub fn clearing_price(&self) -> U256 {
+        self.clearing_price.get()
+    }
+
+    /// Whether `settle()` or `finalize()` has run to completion.
+    pub fn is_settled(&self) -> bool {
+        self.settled.get()
+    }
+
+    /// Keeps purchases closed until `sale_start`. Zero opens the sale as soon
+    /// as it is initialised.
+    pub fn set_sale_start(&mut self, sale_start: U256) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        self.check_not_started()?;
+
+        if sale_start >= self.sale_end.get() {
+            return Err(TokenSaleErrors::InvalidSchedule(InvalidSchedule {}));
+        }
+
+        self.sale_start.set(sale_start);
+
+        Ok(())
+    }
+
+    /// Replaces the phase schedule. Phase `i` is open from `starts[i]` until
+    /// just before `ends[i]`, phases must be ordered and may not overlap, and
+    /// nothing can be bought between them. A non-zero `prices[i]` replaces the
+    /// tier schedule during the phase and a non-zero `wallet_caps[i]` limits
+    /// the sale tokens each wallet can buy in it.
+    pub fn set_phases(
+        &mut self,
+        starts: Vec<U256>,
+        ends: Vec<U256>,
+        prices: Vec<U256>,
+        wallet_caps: Vec<U256>,
+    ) -> Result<(), TokenSaleErrors> {
+        self.only_admin()?;
+
+        self.check_not_started()?;
+
+        if starts.len() != ends.len()
+            || starts.len() != prices.len()
+            || starts.len() != wallet_caps.len()
+        {
+            return Err(TokenSaleErrors::LengthMismatch(LengthMismatch {}));
+        }
+
+        if starts.iter().zip(&ends).any(|(start, end)| start >= end)
+            || starts
+                .iter()
+                .skip(1)
+                .zip(&ends)
+                .any(|(start, end)| start < end)
+        {
+            return Err(TokenSaleErrors::InvalidSchedule(InvalidSchedule {}));
+        }
+
+        while self.phase_starts.pop().is_some() {}
+        while self.phase_ends.pop().is_some() {}
+        while self.phase_prices.pop().is_some() {}
+        while self.phase_wallet_caps.pop().is_some() {}
+
+        let phases = starts.into_iter().zip(ends).zip(prices).zip(wallet_caps);
+        for (((start, end), price), wallet_cap) in phases {
+            self.phase_starts.push(start);
+            self.phase_ends.push(end);
+            self.phase_prices.push(price);
+            self.phase_wallet_caps.push(wallet_cap);
+        }
+
+        Ok(())
+    }
+
+    pub fn phases(&self) -> (Vec<U256>, Vec<U256>, Vec<U256>, Vec<U256>) {
+        let read = |phases: &StorageVec<StorageU256>| -> Vec<U256> {
+            (0..phases.len()).filter_map(|i| phases.get(i)).collect()
+        };
+
+        (
+            read(&self.phase_starts),
+            read(&self.phase_ends),
+            read(&self.phase_prices),
+            read(&self.phase_wallet_caps),
+        )
+    }
+
+    /// Index of the phase open at the current block time.
+    pub fn current_phase(&self) -> Result<U256, TokenSaleErrors> {
+        self.active_phase()
+            .map(U256::from)
+            .ok_or(TokenSaleErrors::PhaseClosed(PhaseClosed {}))
+    }
+
+    pub fn price_tiers(&self) -> (Vec<U256>, Vec<U256>) {
+        let thresholds = (0..self.tier_thresholds.len())
+            .filter_map(|i| self.tier_thresholds.get(i))
+            .collect();
+        let prices = (0..self.tier_prices.len())
+            .filter_map(|i| self.tier_prices.get(i))
+            .collect();
+
+        (thresholds, prices)
+    }
+
+    pub fn current_price(&self) -> Result<U256, TokenSaleErrors> {
+        self.calculate_price()
+    }
+
+    pub fn raised_usd(&self) -> U256 {
+        self.raised_usd.get()
+    }
+
+    /// Everything the sale page shows, in one call.
+    pub fn sale_state(&self) -> Result<SaleState, TokenSaleErrors> {
+        Ok(SaleState {
+            admin: self.admin.get(),
+            token: self.token.get(),
+            oracle: self.oracle.get(),
+            totalSupply: self.total_supply.get(),
+            tokensSold: self.tokens_sold.get(),
+            raisedUsd: self.raised_usd.get(),
+            softCap: self.soft_cap.get(),
+            saleStart: self.sale_start.get(),
+            saleEnd: self.sale_end.get(),
+            currentPrice: self.calculate_price()?,
+        })
+    }
+
+    /// Quotes `buy_token(amount, token_in, false)`: the sale tokens bought,
+    /// the average USD price paid per token, the price right after the
+    /// purchase and the supply left.
+    pub fn quote_buy(
+        &self,
+        amount: U256,
+        token_in: Address,
+    ) -> Result<(U256, U256, U256, U256), TokenSaleErrors> {
+        let (amount_out, usd_value) = self.quote(amount, token_in)?;
+
+        let available = math::sub(self.total_supply.get(), self.tokens_sold.get())?;
+        if amount_out > available {
+            return Err(TokenSaleErrors::SoldOut(SoldOut {}));
+        }
+
+        let effective_price = if amount_out.is_zero() {
+            U256::ZERO
+        } else {
+            let unit_out = math::pow10(self.token_decimals.get().to::<u8>())?;
+            math::mul_div(usd_value, unit_out, amount_out)?
+        };
+
+        let tokens_sold = math::add(self.tokens_sold.get(), amount_out)?;
+
+        Ok((
+            amount_out,
+            effective_price,
+            self.price_at(tokens_sold)?,
+            available - amount_out,
+        ))
+    }
+
+    pub fn calculate_amount_out(
+        &self,
+        amount: U256,
+        token_in: Address,
+    ) -> Result<U256, TokenSaleErrors> {
+        let (amount_out, _) = self.quote(amount, token_in)?;
+        Ok(amount_out)
+    }
+
+    /// USD value, at 18 decimals, that buys exactly `amount_out` sale tokens
+    /// from the current `tokens_sold`.
+    pub fn quote_cost(&self, amount_out: U256) -> Result<U256, TokenSaleErrors> {
+        self.usd_for_tokens(amount_out)
+    }
+
+    /// Amount of `token_in` `buy_exact_tokens` charges for `amount_out`.
+    pub fn quote_amount_in(
+        &self,
+        amount_out: U256,
+        token_in: Address,
+    ) -> Result<U256, TokenSaleErrors> {
+        self.amount_in_for_tokens(amount_out, token_in)
+    }
+}
+
+impl TokenSale {
+    /// Books a purchase of `amount` of `token_in` for `msg::sender()` and
+    /// returns the amount of `token_in` actually spent together with the sale
+    /// tokens bought. Moving the funds is left to the caller.
+    fn record_purchase(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        allow_partial: bool,
+    ) -> Result<(U256, U256), TokenSaleErrors> {
+        self.check_open(amount, token_in)?;
+
+        let (mut amount_out, mut usd_value) = self.quote(amount, token_in)?;
+        let mut amount = amount;
+
+        let remaining = math::sub(self.total_supply.get(), self.tokens_sold.get())?;
+        if amount_out > remaining {
+            if !allow_partial || remaining.is_zero() {
+                return Err(TokenSaleErrors::SoldOut(SoldOut {}));
+            }
+
+            // Only the share of `amount` that pays for the remaining supply is
+            // pulled, rounded up so the partial fill never undercharges.
+            let remaining_usd = self.usd_for_tokens(remaining)?.min(usd_value);
+            amount = math::mul_div_up(amount, remaining_usd, usd_value)?;
+            usd_value = remaining_usd;
+            amount_out = remaining;
+        }
+
+        self.book_purchase(amount, token_in, amount_out, usd_value)?;
+
+        Ok((amount, amount_out))
+    }
+
+    /// Books `amount_out` sale tokens bought by `msg::sender()` for `amount`
+    /// of `token_in` worth `usd_value`, against the phase and wallet limits.
+    fn book_purchase(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        amount_out: U256,
+        usd_value: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        if let Some(phase) = self.active_phase() {
+            self.record_phase_purchase(phase, amount_out)?;
+        }
+
+        self.record_wallet_usd(usd_value)?;
+
+        self.tokens_sold
+            .set(math::add(self.tokens_sold.get(), amount_out)?);
+
+        self.add_contribution(token_in, amount)?;
+
+        self.raised_usd
+            .set(math::add(self.raised_usd.get(), usd_value)?);
+
+        Ok(())
+    }
+
+    /// Books a batch auction bid for `msg::sender()`. Moving the funds is left
+    /// to the caller.
+    fn record_bid(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+        max_price: U256,
+        hint: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        if !self.is_batch_auction() {
+            return Err(TokenSaleErrors::InvalidSaleMode(InvalidSaleMode {}));
+        }
+
+        self.check_open(amount, token_in)?;
+
+        // Zero ends the list of bid price levels.
+        if max_price.is_zero() || max_price < self.current_price_usd.get() {
+            return Err(TokenSaleErrors::BidTooLow(BidTooLow {}));
+        }
+
+        let usd_value = self.usd_value(amount, token_in)?;
+        if usd_value < MIN_BID_USD {
+            return Err(TokenSaleErrors::BelowMinimum(BelowMinimum {}));
+        }
+
+        self.record_wallet_usd(usd_value)?;
+        self.add_to_bid_level(max_price, usd_value, hint)?;
+
+        let mut bid = self.bids.grow();
+        bid.bidder.set(msg::sender());
+        bid.token.set(token_in);
+        bid.amount.set(amount);
+        bid.usd_value.set(usd_value);
+        bid.max_price.set(max_price);
+
+        self.add_contribution(token_in, amount)
+    }
+
+    /// Adds `usd_value` to the demand at `price`, inserting the level into the
+    /// list of bid price levels, highest first, if it is new. The search for
+    /// its place starts at `hint`, which must be an existing level above
+    /// `price`, or at the highest level if `hint` is zero. Walking the whole
+    /// list would let cheap bids at many prices make every later one costly.
+    fn add_to_bid_level(
+        &mut self,
+        price: U256,
+        usd_value: U256,
+        hint: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        let level_usd = self.bid_level_usd.get(price);
+
+        // Every bid is worth at least `MIN_BID_USD`, so only new levels are
+        // empty.
+        if level_usd.is_zero() {
+            if !hint.is_zero() && (hint <= price || self.bid_level_usd.get(hint).is_zero()) {
+                return Err(TokenSaleErrors::InvalidBidHint(InvalidBidHint {}));
+            }
+
+            let (previous, next) = self.bid_level_position(hint, price);
+
+            self.bid_level_next.insert(price, next);
+            if previous.is_zero() {
+                self.bid_level_head.set(price);
+            } else {
+                self.bid_level_next.insert(previous, price);
+            }
+        }
+
+        self.bid_level_usd
+            .insert(price, math::add(level_usd, usd_value)?);
+
+        Ok(())
+    }
+
+    /// Walks the bid price levels down from `start`, or from the highest one
+    /// if `start` is zero, to the last level above `price` and the one after
+    /// it.
+    fn bid_level_position(&self, start: U256, price: U256) -> (U256, U256) {
+        let mut previous = start;
+        let mut next = if start.is_zero() {
+            self.bid_level_head.get()
+        } else {
+            self.bid_level_next.get(start)
+        };
+
+        while next > price {
+            previous = next;
+            next = self.bid_level_next.get(next);
+        }
+
+        (previous, next)
+    }
+
+    /// One clearing step of `settle()`: visits the next bid price level, or
+    /// clears at the reserve side once they are all visited.
+    fn clear_next_level(&mut self, unit_out: U256) -> Result<(), TokenSaleErrors> {
+        let last = self.clearing_level.get();
+        let level = if last.is_zero() {
+            self.bid_level_head.get()
+        } else {
+            self.bid_level_next.get(last)
+        };
+
+        let higher = self.clearing_higher_usd.get();
+        let supply = self.total_supply.get();
+        let group = self.bid_level_usd.get(level);
+
+        let cleared = if level.is_zero() {
+            Some(auction::clear_remaining(
+                higher,
+                supply,
+                unit_out,
+                self.current_price_usd.get(),
+            )?)
+        } else {
+            auction::clear_level(higher, group, level, supply, unit_out)?
+        };
+
+        if let Some((price, fill)) = cleared {
+            self.clearing_price.set(price);
+            self.clearing_fill.set(fill);
+            self.auction_cleared.set(true);
+        } else {
+            self.clearing_higher_usd.set(math::add(higher, group)?);
+            self.clearing_level.set(level);
+        }
+
+        Ok(())
+    }
+
+    /// Books an overflow sale contribution for `msg::sender()`. Moving the
+    /// funds is left to the caller.
+    fn record_contribution(
+        &mut self,
+        amount: U256,
+        token_in: Address,
+    ) -> Result<(), TokenSaleErrors> {
+        self.check_open(amount, token_in)?;
+
+        let usd_value = self.usd_value(amount, token_in)?;
+        self.record_wallet_usd(usd_value)?;
+
+        let contributed = math::add(self.contributed_usd.get(msg::sender()), usd_value)?;
+        self.contributed_usd.insert(msg::sender(), contributed);
+
+        self.raised_usd
+            .set(math::add(self.raised_usd.get(), usd_value)?);
+
+        self.add_contribution(token_in, amount)
+    }
+
+    /// Books the pro-rata allocation of `account` in a finalized overflow
+    /// sale and moves the unspent share of its payments to excess. Runs once,
+    /// on the first `claim()`, `refund()` or `refund_excess()` of `account`.
+    fn allocate_overflow(&mut self, account: Address) -> Result<(), TokenSaleErrors> {
+        if !OVERFLOW_SALE_ENABLED {
+            return Ok(());
+        }
+
+        let usd_value = self.contributed_usd.get(account);
+        if usd_value.is_zero() {
+            return Ok(());
+        }
+
+        self.contributed_usd.insert(account, U256::ZERO);
+
+        let total_usd = self.overflow_total_usd.get();
+        let excess_usd = self.overflow_excess_usd.get();
+
+        let amount_out = if excess_usd.is_zero() {
+            let unit_out = math::pow10(self.token_decimals.get().to::<u8>())?;
+            math::mul_div(usd_value, unit_out, self.current_price_usd.get())?
+        } else {
+            math::mul_div(self.total_supply.get(), usd_value, total_usd)?
+        };
+
+        let allocation = math::add(self.allocations.get(account), amount_out)?;
+        self.allocations.setter(account).set(allocation);
+
+        if excess_usd.is_zero() {
+            return Ok(());
+        }
+
+        for i in 0..self.accepted_token_list.len() {
+            let token = self.accepted_token_list.get(i).unwrap();
+            let contribution = self.contributions.get(account).get(token);
+            let excess = math::mul_div(contribution, excess_usd, total_usd)?;
+            if excess.is_zero() {
+                continue;
+            }
+
+            let total_excess = math::add(self.excess.get(account).get(token), excess)?;
+            self.excess.setter(account).setter(token).set(total_excess);
+
+            self.contributions
+                .setter(account)
+                .setter(token)
+                .set(math::sub(contribution, excess)?);
+        }
+
+        Ok(())
+    }
+
+    /// Fills bid `i` at the clearing `price`, in full above it and by `fill`
+    /// at it, and books the rest of its payment as excess.
+    fn settle_bid(
+        &mut self,
+        i: usize,
+        price: U256,
+        fill: U256,
+        unit_out: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        let (bidder, token, amount, usd_value, max_price) = {
+            let bid = self.bids.getter(i).unwrap();
+            (
+                bid.bidder.get(),
+                bid.token.get(),
+                bid.amount.get(),
+                bid.usd_value.get(),
+                bid.max_price.get(),
+            )
+        };
+
+        let spent_usd = if max_price > price {
+            usd_value
+        } else if max_price == price {
+            math::mul_div(usd_value, fill, WAD)?
+        } else {
+            U256::ZERO
+        };
+
+        // The spent share of the payment is rounded up, so the excess never
+        // pays out more than was left over.
+        let spent = if usd_value.is_zero() {
+            U256::ZERO
+        } else {
+            math::mul_div_up(amount, spent_usd, usd_value)?
+        };
+        let excess = math::sub(amount, spent)?;
+
+        let amount_out = math::mul_div(spent_usd, unit_out, price)?;
+        let allocation = math::add(self.allocations.get(bidder), amount_out)?;
+        self.allocations.setter(bidder).set(allocation);
+
+        self.tokens_sold
+            .set(math::add(self.tokens_sold.get(), amount_out)?);
+        self.raised_usd
+            .set(math::add(self.raised_usd.get(), spent_usd)?);
+
+        if excess.is_zero() {
+            return Ok(());
+        }
+
+        let total_excess = math::add(self.excess.get(bidder).get(token), excess)?;
+        self.excess.setter(bidder).setter(token).set(total_excess);
+
+        let contribution = math::sub(self.contributions.get(bidder).get(token), excess)?;
+        self.contributions
+            .setter(bidder)
+            .setter(token)
+            .set(contribution);
+
+        self.set_collected_amount(token, math::sub(self.collected_amount.get(token), excess)?);
+
+        Ok(())
+    }
+
+    /// Sends `account` its unspent batch auction payments in every token ever
+    /// accepted and reports whether there were any.
+    fn pay_excess(&mut self, account: Address) -> Result<bool, TokenSaleErrors> {
+        let mut paid = false;
+
+        for i in 0..self.accepted_token_list.len() {
+            let token = self.accepted_token_list.get(i).unwrap();
+            let amount = self.excess.get(account).get(token);
+            if amount.is_zero() {
+                continue;
+            }
+
+            self.excess.setter(account).setter(token).set(U256::ZERO);
+            self.send_funds(token, amount, account)?;
+            paid = true;
+        }
+
+        Ok(paid)
+    }
+
+    fn check_open(&self, amount: U256, token_in: Address) -> Result<(), TokenSaleErrors> {
+        let now = U256::from(block::timestamp());
+
+        if now < self.sale_start.get() {
+            return Err(TokenSaleErrors::SaleNotStarted(SaleNotStarted {}));
+        }
+
+        if self.sale_end.get() < now {
+            return Err(TokenSaleErrors::SaleEnded(SaleEnded {}));
+        }
+
+        if !self.phase_starts.is_empty() && self.active_phase().is_none() {
+            return Err(TokenSaleErrors::PhaseClosed(PhaseClosed {}));
+        }
+
+        if amount.is_zero() {
+            return Err(TokenSaleErrors::ZeroAmount(ZeroAmount {}));
+        }
+
+        if !self.supported_tokens.get(token_in) {
+            return Err(TokenSaleErrors::UnsupportedToken(UnsupportedToken {}));
+        }
+
+        Ok(())
+    }
+
+    /// Sale parameters can only change before anything has been bought, bid
+    /// or contributed, in any sale mode.
+    fn check_not_started(&self) -> Result<(), TokenSaleErrors> {
+        let started = !self.tokens_sold.get().is_zero()
+            || !self.raised_usd.get().is_zero()
+            || !self.bids.is_empty()
+            || (0..self.accepted_token_list.len())
+                .filter_map(|i| self.accepted_token_list.get(i))
+                .any(|token| !self.collected_amount.get(token).is_zero());
+
+        if started {
+            return Err(TokenSaleErrors::SaleAlreadyStarted(SaleAlreadyStarted {}));
+        }
+
+        Ok(())
+    }
+
+    /// Batch auctions and overflow sales hold every payment until `settle()`
+    /// or `finalize()` has split it into spent and excess.
+    fn check_settled(&self) -> Result<(), TokenSaleErrors> {
+        if !self.sale_mode.get().is_zero() && !self.settled.get() {
+            return Err(TokenSaleErrors::NotSettled(NotSettled {}));
+        }
+
+        Ok(())
+    }
+
+    /// Applies the per-wallet contribution limits to a purchase of
+    /// `usd_value` by `msg::sender()`.
+    fn record_wallet_usd(&mut self, usd_value: U256) -> Result<(), TokenSaleErrors> {
+        if usd_value < self.min_contribution_usd.get() {
+            return Err(TokenSaleErrors::BelowMinimum(BelowMinimum {}));
+        }
+
+        let spent = math::add(self.wallet_usd.get(msg::sender()), usd_value)?;
+        let max_usd = self.max_contribution_usd.get();
+        if !max_usd.is_zero() && spent > max_usd {
+            return Err(TokenSaleErrors::AboveMaximum(AboveMaximum {}));
+        }
+
+        self.wallet_usd.insert(msg::sender(), spent);
+
+        Ok(())
+    }
+
+    /// Shared by every admin entrypoint rather than inlined into each, to
+    /// keep the deployed code small.
+    fn only_admin(&self) -> Result<(), TokenSaleErrors> {
+        if msg::sender() != self.admin.get() {
+            return Err(TokenSaleErrors::NotAdmin(NotAdmin {}));
+        }
+
+        Ok(())
+    }
+
+    /// Open entrypoints are closed while either gate is set.
+    fn check_public(&self) -> Result<(), TokenSaleErrors> {
+        if !self.merkle_root.get().is_zero() {
+            return Err(TokenSaleErrors::WhitelistOnly(WhitelistOnly {}));
+        }
+
+        if !self.voucher_signer.get().is_zero() {
+            return Err(TokenSaleErrors::VoucherRequired(VoucherRequired {}));
+        }
+
+        Ok(())
+    }
+
+    fn active_phase(&self) -> Option<usize> {
+        let now = U256::from(block::timestamp());
+
+        (0..self.phase_starts.len()).find(|&i| {
+            self.phase_starts.get(i).unwrap_or_default() <= now
+                && now < self.phase_ends.get(i).unwrap_or_default()
+        })
+    }
+
+    fn record_phase_purchase(
+        &mut self,
+        phase: usize,
+        amount_out: U256,
+    ) -> Result<(), TokenSaleErrors> {
+        let key = U256::from(phase);
+        let purchased = math::add(self.phase_purchased.get(msg::sender()).get(key), amount_out)?;
+
+        let cap = self.phase_wallet_caps.get(phase).unwrap_or_default();
+        if !cap.is_zero() && purchased > cap {
+            return Err(TokenSaleErrors::WalletCapExceeded(WalletCapExceeded {}));
+        }
+
+        self.phase_purchased
+            .setter(msg::sender())
+            .setter(key)
+            .set(purchased);
+
+        Ok(())
+    }
+
+    fn is_batch_auction(&self) -> bool {
+        BATCH_AUCTION_ENABLED && self.sale_mode.get().to::<u8>() == SALE_MODE_BATCH
+    }
+
+    fn is_overflow_sale(&self) -> bool {
+        OVERFLOW_SALE_ENABLED && self.sale_mode.get().to::<u8>() == SALE_MODE_OVERFLOW
+    }
+
+    fn add_contribution(&mut self, token_in: Address, amount: U256) -> Result<(), TokenSaleErrors> {
+        self.set_collected_amount(
+            token_in,
+            math::add(self.collected_amount.get(token_in), amount)?,
+        );
+
+        let contribution = math::add(self.contributions.get(msg::sender()).get(token_in), amount)?;
+        self.contributions
+            .setter(msg::sender())
+            .setter(token_in)
+            .set(contribution);
+
+        Ok(())
+    }
+
+    /// Books a purchase paid with `received` of `token_in`, sends back what a
+    /// partial fill leaves unused and delivers the sale tokens.
+    fn complete_purchase(
+        &mut self,
+        received: U256,
+        token_in: Address,
+        allow_partial: bool,
+    ) -> Result<(), TokenSaleErrors> {
+        let (used, amount_out) = self.record_purchase(received, token_in, allow_partial)?;
+
+        if received > used {
+            self.transfer_token(token_in, received - used, msg::sender())?;
+        }
+
+        self.deliver(amount_out)
+    }
+
+    fn deliver(&mut self, amount_out: U256) -> Result<(), TokenSaleErrors> {
+        if self.is_escrowed() {
+            let allocation = math::add(self.allocations.get(msg::sender()), amount_out)?;
+            self.allocations.setter(msg::sender()).set(allocation);
+        } else {
+            self.transfer_token(self.token.get(), amount_out, msg::sender())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the sale tokens bought with `amount` and the USD value of
+    /// `amount`, both priced through the same oracle read.
+    fn quote(&self, amount: U256, token_in: Address) -> Result<(U256, U256), TokenSaleErrors> {
+        let usd_value = self.usd_value(amount, token_in)?;
+
+        Ok((self.tokens_for_usd(usd_value)?, usd_value))
+    }
+
+    /// Prices are 18-decimal USD per whole token, so `amount` is divided by
+    /// the payment token's unit.
+    fn usd_value(&self, amount: U256, token_in: Address) -> Result<U256, TokenSaleErrors> {
+        let price = self.get_price(token_in)?;
+        let unit_in = math::pow10(self.payment_decimals.get(token_in).to::<u8>())?;
+
+        math::mul_div(amount, price, unit_in)
+    }
+
+    /// Sale tokens bought with `usd_value` starting from the current
+    /// `tokens_sold`. The curve modes integrate the price over the purchase
+    /// and tiered pricing splits it at every threshold it crosses.
+    fn tokens_for_usd(&self, usd_value: U256) -> Result<U256, TokenSaleErrors> {
+        let price = self.calculate_price()?;
+        let rate = self.curve_rate.get();
+        let unit_out = math::pow10(self.token_decimals.get().to::<u8>())?;
+
+        match self.pricing_mode.get().to::<u8>() {
+            PRICING_LINEAR if BONDING_CURVES_ENABLED => {
+                curve::linear_amount_out(price, rate, usd_value, unit_out)
+            }
+            PRICING_EXPONENTIAL if BONDING_CURVES_ENABLED => {
+                curve::exponential_amount_out(price, rate, usd_value, unit_out)
+            }
+            PRICING_TIERED if self.phase_price().is_none() => curve::tiered_amount_out(
+                self.current_price_usd.get(),
+                &self.price_tier_list(),
+                self.tokens_sold.get(),
+                usd_value,
+                unit_out,
+            ),
+            _ => math::mul_div(usd_value, unit_out, price),
+        }
+    }
+
+    /// Inverse of `tokens_for_usd`, rounded up.
+    fn usd_for_tokens(&self, amount_out: U256) -> Result<U256, TokenSaleErrors> {
+        let price = self.calculate_price()?;
+        let rate = self.curve_rate.get();
+        let unit_out = math::pow10(self.token_decimals.get().to::<u8>())?;
+
+        match self.pricing_mode.get().to::<u8>() {
+            PRICING_LINEAR if BONDING_CURVES_ENABLED => {
+                curve::linear_cost(price, rate, amount_out, unit_out)
+            }
+            PRICING_EXPONENTIAL if BONDING_CURVES_ENABLED => {
+                curve::exponential_cost(price, rate, amount_out, unit_out)
+            }
+            PRICING_TIERED if self.phase_price().is_none() => curve::tiered_cost(
+                self.current_price_usd.get(),
+                &self.price_tier_list(),
+                self.tokens_sold.get(),
+                amount_out,
+                unit_out,
+            ),
+            _ => math::mul_div_up(amount_out, price, unit_out),
+        }
+    }
+
+    /// Inverse of `quote`, rounded up so the sale is never undercharged.
+    fn amount_in_for_tokens(
+        &self,
+        amount_out: U256,
+        token_in: Address,
+    ) -> Result<U256, TokenSaleErrors> {
+        let usd_value = self.usd_for_tokens(amount_out)?;
+        let price = self.get_price(token_in)?;
+        let unit_in = math::pow10(self.payment_decimals.get(token_in).to::<u8>())?;
+
+        math::mul_div_up(usd_value, unit_in, price)
+    }
+
+    fn soft_cap_reached(&self) -> bool {
+        self.raised_usd.get() >= self.soft_cap.get()
+    }
+
+    /// Purchases are held by the contract and released through `claim()`
+    /// when vesting is on or when a soft cap might still force refunds.
+    fn is_escrowed(&self) -> bool {
+        self.vesting_enabled.get() || !self.soft_cap.get().is_zero()
+    }
+
+    /// Spot price at the current `tokens_sold`.
+    fn calculate_price(&self) -> Result<U256, TokenSaleErrors> {
+        self.price_at(self.tokens_sold.get())
+    }
+
+    /// Price once `tokens_sold` tokens are sold, at the current block time.
+    fn price_at(&self, tokens_sold: U256) -> Result<U256, TokenSaleErrors> {
+        let initial_price = self.current_price_usd.get();
+        let rate = self.curve_rate.get();
+        let unit_out = math::pow10(self.token_decimals.get().to::<u8>())?;
+
+        match self.pricing_mode.get().to::<u8>() {
+            PRICING_LINEAR if BONDING_CURVES_ENABLED => {
+                curve::linear_price(initial_price, rate, tokens_sold, unit_out)
+            }
+            PRICING_EXPONENTIAL if BONDING_CURVES_ENABLED => {
+                curve::exponential_price(initial_price, rate, tokens_sold, unit_out)
+            }
+            PRICING_DUTCH if DUTCH_AUCTION_ENABLED => self.auction_price(),
+            _ => Ok(self.phase_price().unwrap_or_else(|| {
+                curve::tiered_price(initial_price, &self.price_tier_list(), tokens_sold)
+            })),
+        }
+    }
+
+    /// Price of the phase open at the current block time, if it sets one. It
+    /// overrides the tiers for the whole purchase.
+    fn phase_price(&self) -> Option<U256> {
+        self.active_phase()
+            .and_then(|phase| self.phase_prices.get(phase))
+            .filter(|price| !price.is_zero())
+    }
+
+    /// Dutch auction price at the current block time.
+    fn auction_price(&self) -> Result<U256, TokenSaleErrors> {
+        let start_price = self.auction_start_price.get();
+        let floor_price = self.auction_floor_price.get();
+        let start = self.auction_start.get();
+        let end = self.sale_end.get();
+        let now = U256::from(block::timestamp());
+
+        if now <= start {
+            return Ok(start_price);
+        }
+
+        if now >= end {
+            return Ok(floor_price);
+        }
+
+        let decay = math::mul_div(
+            math::sub(start_price, floor_price)?,
+            now - start,
+            end - start,
+        )?;
+
+        math::sub(start_price, decay)
+    }
+
+    /// The tier schedule as `(threshold, price)` pairs.
+    fn price_tier_list(&self) -> Vec<(U256, U256)> {
+        (0..self.tier_thresholds.len())
+            .filter_map(|i| Some((self.tier_thresholds.get(i)?, self.tier_prices.get(i)?)))
+            .collect()
+    }
+
+    fn get_price(&self, token_in: Address) -> Result<U256, TokenSaleErrors> {
+        let source = self.price_sources.get(token_in);
+
+        if CHAINLINK_ENABLED && source.kind.get().to::<u8>() == PRICE_SOURCE_CHAINLINK {
+            return self.get_chainlink_price(source.oracle.get(), source.heartbeat.get());
+        }
+
+        let oracle_addr = source.oracle.get();
+        IOracle::new(oracle_addr)
+            .get_price(&*self, source.index.get().to::<u8>())
+            .map_err(|_| oracle_call_failed(oracle_addr))
+    }
+
+    /// Reads `latestRoundData` from a Chainlink feed and scales the answer
+    /// from the feed's `decimals()` to 18.
+    fn get_chainlink_price(&self, feed: Address, heartbeat: U256) -> Result<U256, TokenSaleErrors> {
+        self.check_sequencer()?;
+
+        let aggregator = IAggregatorV3::new(feed);
+        let (_, answer, _, updated_at, _) = aggregator
+            .latest_round_data(&*self)
+            .map_err(|_| oracle_call_failed(feed))?;
+
+        if answer <= I256::ZERO {
+            return Err(TokenSaleErrors::InvalidPrice(InvalidPrice {}));
+        }
+
+        if math::add(updated_at, heartbeat)? < U256::from(block::timestamp()) {
+            return Err(TokenSaleErrors::StalePrice(StalePrice {}));
+        }
+
+        let decimals = aggregator
+            .decimals(&*self)
+            .map_err(|_| oracle_call_failed(feed))?;
+
+        math::scale(answer.into_raw(), decimals, 18)
+    }
+
+    /// Chainlink answers cannot be trusted while the sequencer is down or
+    /// shortly after it comes back up.
+    fn check_sequencer(&self) -> Result<(), TokenSaleErrors> {
+        let feed = self.sequencer_uptime_feed.get();
+        if feed.is_zero() {
+            return Ok(());
+        }
+
+        let (_, answer, started_at, _, _) = IAggregatorV3::new(feed)
+            .latest_round_data(&*self)
+            .map_err(|_| oracle_call_failed(feed))?;
+
+        if !answer.is_zero() {
+            return Err(TokenSaleErrors::SequencerDown(SequencerDown {}));
+        }
+
+        if U256::from(block::timestamp())
+            < math::add(started_at, U256::from(SEQUENCER_GRACE_PERIOD))?
+        {
+            return Err(TokenSaleErrors::GracePeriodNotOver(GracePeriodNotOver {}));
+        }
+
+        Ok(())
+    }
+
+    fn transfer_token(
+        &mut self,
+        token_addr: Address,
+        amount: U256,
+        to: Address,
+    ) -> Result<(), TokenSaleErrors> {
+        let data = transferCall { to, value: amount }.abi_encode();
+
+        if !self.call_token(token_addr, &data) {
+            return Err(TokenSaleErrors::TransferFailed(TransferFailed {
+                token: token_addr,
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Pulls `amount` of `token_in` from `msg::sender()` and returns how much
+    /// actually arrived, which is less for fee-on-transfer tokens.
+    fn pull_funds(&mut self, token_in: Address, amount: U256) -> Result<U256, TokenSaleErrors> {
+        let before = self.balance_of(token_in)?;
+
+        self.transfer_token_from(token_in, amount, msg::sender(), contract::address())?;
+
+        math::sub(self.balance_of(token_in)?, before)
+    }
+
+    fn balance_of(&self, token: Address) -> Result<U256, TokenSaleErrors> {
+        IERC20::new(token)
+            .balance_of(&*self, contract::address())
+            .map_err(|_| TokenSaleErrors::TransferFromFailed(TransferFromFailed { token }))
+    }
+
+    fn transfer_token_from(
+        &mut self,
+        token_addr: Address,
+        amount: U256,
+        from: Address,
+        to: Address,
+    ) -> Result<(), TokenSaleErrors> {
+        let data = transferFromCall {
+            from,
+            to,
+            value: amount,
+        }
+        .abi_encode();
+
+        if !self.call_token(token_addr, &data) {
+            return Err(TokenSaleErrors::TransferFromFailed(TransferFromFailed {
+                token: token_addr,
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Pulls `amount` of `token` from `msg::sender()` through Permit2, which
+    /// checks it against the signed permit.
+    fn transfer_token_permit2(
+        &mut self,
+        token: Address,
+        amount: U256,
+        nonce: U256,
+        deadline: U256,
+        signature: Bytes,
+    ) -> Result<(), TokenSaleErrors> {
+        let data = IPermit2::permitTransferFromCall {
+            permit: IPermit2::PermitTransferFrom {
+                permitted: IPermit2::TokenPermissions { token, amount },
+                nonce,
+                deadline,
+            },
+            transferDetails: IPermit2::SignatureTransferDetails {
+                to: contract::address(),
+                requestedAmount: amount,
+            },
+            owner: msg::sender(),
+            signature: signature.0.into(),
+        }
+        .abi_encode();
+
+        let mut permit2 = self.permit2.get();
+        if permit2.is_zero() {
+            permit2 = PERMIT2;
+        }
+
+        if call::call(Call::new_in(self), permit2, &data).is_err() {
+            return Err(TokenSaleErrors::TransferFromFailed(TransferFromFailed {
+                token,
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Calls `token` with ERC-20 calldata and reports whether it succeeded.
+    /// Tokens like USDT return no data at all, so empty return data from a
+    /// contract counts as success, the same way `SafeERC20` treats it.
+    fn call_token(&mut self, token: Address, data: &[u8]) -> bool {
+        match call::call(Call::new_in(self), token, data) {
+            Ok(ret) if ret.is_empty() => token.has_code(),
+            Ok(ret) => bool::abi_decode(&ret, false).unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    /// Pays out a payment token, treating `Address::ZERO` as the native gas
+    /// currency.
+    fn send_funds(
+        &mut self,
+        token_addr: Address,
+        amount: U256,
+        to: Address,
+    ) -> Result<(), TokenSaleErrors> {
+        if token_addr.is_zero() {
+            return call::transfer_eth(to, amount).map_err(|_| {
+                TokenSaleErrors::TransferFailed(TransferFailed {
+                    token: Address::ZERO,
+                })
+            });
+        }
+
+        self.transfer_token(token_addr, amount, to)
+    }
+
+    fn add_token(
+        &mut self,
+        token: Address,
+        oracle: Address,
+        price_index: u8,
+    ) -> Result<(), TokenSaleErrors> {
+        self.set_source(token, oracle, price_index);
+
+        if self.supported_tokens.get(token) {
+            return Ok(());
+        }
+
+        let decimals = self.read_decimals(token)?;
+        self.payment_decimals.insert(token, U8::from(decimals));
+
+        self.supported_tokens.insert(token, true);
+        self.supported_token_list.push(token);
+
+        // Payments stay owed in a token after it is removed, so everything
+        // settled per token walks this append-only list instead.
+        if !self.accepted_tokens.get(token) {
+            self.accepted_tokens.insert(token, true);
+            self.accepted_token_list.push(token);
+        }
+
+        Ok(())
+    }
+
+    /// `Address::ZERO` is the native gas currency, which has 18 decimals.
+    fn read_decimals(&self, token: Address) -> Result<u8, TokenSaleErrors> {
+        if token.is_zero() {
+            return Ok(18);
+        }
+
+        IERC20::new(token)
+            .decimals(&*self)
+            .map_err(|_| TokenSaleErrors::DecimalsCallFailed(DecimalsCallFailed { token }))
+    }
+
+    fn set_source(&mut self, token: Address, oracle: Address, price_index: u8) {
+        let mut source = self.price_sources.setter(token);
+        source.kind.set(U8::from(PRICE_SOURCE_ORACLE));
+        source.oracle.set(oracle);
+        source.index.set(U8::from(price_index));
+        source.heartbeat.set(U256::ZERO);
+    }
+
+    fn set_collected_amount(&mut self, token_addr: Address, new_amount: U256) {
+        let mut amount_setter = self.collected_amount.setter(token_addr);
+        amount_setter.set(new_amount);
+    }
+}
+
+fn oracle_call_failed(oracle: Address) -> TokenSaleErrors {
+    TokenSaleErrors::OracleCallFailed(OracleCallFailed { oracle })
+}
+
+/// Fails calls into an optional feature this build was made without, see
+/// `BONDING_CURVES_ENABLED`.
+fn check_enabled(enabled: bool) -> Result<(), TokenSaleErrors> {
+    if !enabled {
+        return Err(TokenSaleErrors::FeatureDisabled(FeatureDisabled {}));
+    }
+
+    Ok(())
 }
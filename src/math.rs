@@ -0,0 +1,315 @@
+use stylus_sdk::alloy_primitives::U256;
+
+use crate::{
+    constants::WAD,
+    errors::{DivisionByZero, MathOverflow, TokenSaleErrors},
+};
+
+/// 1e36, the working precision of `exp_wad` and `ln_wad`.
+const ONE_36: U256 = U256::from_limbs([12919594847110692864, 54210108624275221, 0, 0]);
+
+/// ln(2) at 36 decimals.
+const LN2_36: U256 = U256::from_limbs([9456716947207598648, 37575583950764745, 0, 0]);
+
+/// How far the at most 29 rounded-down terms of the `e^r` series can leave
+/// their sum below `e^r`, at 36 decimals.
+const EXP_SERIES_ERROR: U256 = U256::from_limbs([64, 0, 0, 0]);
+
+fn overflow() -> TokenSaleErrors {
+    TokenSaleErrors::MathOverflow(MathOverflow {})
+}
+
+pub fn add(a: U256, b: U256) -> Result<U256, TokenSaleErrors> {
+    a.checked_add(b).ok_or_else(overflow)
+}
+
+pub fn sub(a: U256, b: U256) -> Result<U256, TokenSaleErrors> {
+    a.checked_sub(b).ok_or_else(overflow)
+}
+
+pub fn mul(a: U256, b: U256) -> Result<U256, TokenSaleErrors> {
+    a.checked_mul(b).ok_or_else(overflow)
+}
+
+pub fn div(a: U256, b: U256) -> Result<U256, TokenSaleErrors> {
+    a.checked_div(b)
+        .ok_or(TokenSaleErrors::DivisionByZero(DivisionByZero {}))
+}
+
+/// `a * b / denominator`, rounding down.
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256, TokenSaleErrors> {
+    div(mul(a, b)?, denominator)
+}
+
+/// `a * b / denominator`, rounding up.
+pub fn mul_div_up(a: U256, b: U256, denominator: U256) -> Result<U256, TokenSaleErrors> {
+    let product = mul(a, b)?;
+    let quotient = div(product, denominator)?;
+
+    if (product % denominator).is_zero() {
+        Ok(quotient)
+    } else {
+        add(quotient, U256::from(1))
+    }
+}
+
+/// `10^exp`, the unit of a token with `exp` decimals.
+pub fn pow10(exp: u8) -> Result<U256, TokenSaleErrors> {
+    U256::from(10)
+        .checked_pow(U256::from(exp))
+        .ok_or_else(overflow)
+}
+
+/// Rescales `amount` from `from_decimals` to `to_decimals`, rounding down.
+pub fn scale(amount: U256, from_decimals: u8, to_decimals: u8) -> Result<U256, TokenSaleErrors> {
+    if from_decimals < to_decimals {
+        mul(amount, pow10(to_decimals - from_decimals)?)
+    } else {
+        div(amount, pow10(from_decimals - to_decimals)?)
+    }
+}
+
+/// Square root of `x`, rounded up.
+pub fn sqrt_up(x: U256) -> U256 {
+    if x.is_zero() {
+        return x;
+    }
+
+    // Newton's method from a first guess that is never below the root.
+    let mut root = U256::from(1) << ((x.bit_len() + 1) / 2);
+    loop {
+        let next = (root + x / root) >> 1;
+        if next >= root {
+            break;
+        }
+        root = next;
+    }
+
+    if root * root == x {
+        root
+    } else {
+        root + U256::from(1)
+    }
+}
+
+/// Splits `x` into `n * ln 2 + r` with `r < ln 2` and sums the Taylor series
+/// of `e^r` at 36 decimals, rounding every term down.
+fn exp_series(x: U256) -> Result<(U256, usize), TokenSaleErrors> {
+    let x = mul(x, WAD)?;
+    let n = x / LN2_36;
+    let r = x - n * LN2_36;
+
+    if n >= U256::from(195) {
+        return Err(overflow());
+    }
+
+    let mut sum = ONE_36;
+    let mut term = ONE_36;
+    let mut i = 1u64;
+    loop {
+        term = term * r / (ONE_36 * U256::from(i));
+        if term.is_zero() {
+            break;
+        }
+        sum += term;
+        i += 1;
+    }
+
+    Ok((sum, n.to::<usize>()))
+}
+
+/// `e^x` for a non-negative 18-decimal `x`, rounded down.
+pub fn exp_wad(x: U256) -> Result<U256, TokenSaleErrors> {
+    let (sum, n) = exp_series(x)?;
+
+    // `sum` is below 2^121, so shifting before dividing keeps full precision
+    // while it fits.
+    if n < 135 {
+        Ok((sum << n) / WAD)
+    } else {
+        Ok((sum / WAD) << n)
+    }
+}
+
+/// `e^x` for a non-negative 18-decimal `x`, rounded up. The truncated series
+/// is raised by its error bound before dividing, rounding up.
+pub fn exp_wad_up(x: U256) -> Result<U256, TokenSaleErrors> {
+    if x.is_zero() {
+        return Ok(WAD);
+    }
+
+    let (sum, n) = exp_series(x)?;
+    let sum = sum + EXP_SERIES_ERROR;
+
+    if n < 135 {
+        mul_div_up(sum, U256::from(1) << n, WAD)
+    } else {
+        Ok(mul_div_up(sum, U256::from(1), WAD)? << n)
+    }
+}
+
+/// `ln(y)` for an 18-decimal `y >= 1`. Splits `y` into `2^n * m` with
+/// `1 <= m < 2` and sums `ln(m) = 2 * atanh((m - 1) / (m + 1))` at 36
+/// decimals.
+pub fn ln_wad(y: U256) -> Result<U256, TokenSaleErrors> {
+    if y < WAD {
+        return Err(overflow());
+    }
+
+    let n = (y / WAD).bit_len() - 1;
+    let m = mul(y >> n, WAD)?;
+
+    let z = (m - ONE_36) * ONE_36 / (m + ONE_36);
+    let z_squared = z * z / ONE_36;
+
+    let mut sum = U256::ZERO;
+    let mut term = z;
+    let mut k = 1u64;
+    while !term.is_zero() {
+        sum += term / U256::from(k);
+        term = term * z_squared / ONE_36;
+        k += 2;
+    }
+
+    Ok((U256::from(n) * LN2_36 + sum * U256::from(2)) / WAD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wad(x: u64) -> U256 {
+        U256::from(x) * WAD
+    }
+
+    #[test]
+    fn mul_div_rounds_down_and_up() {
+        let (a, b, d) = (U256::from(7), U256::from(3), U256::from(2));
+        assert_eq!(mul_div(a, b, d).ok(), Some(U256::from(10)));
+        assert_eq!(mul_div_up(a, b, d).ok(), Some(U256::from(11)));
+
+        // Exact quotients are not rounded up.
+        assert_eq!(mul_div_up(a, U256::from(4), d).ok(), Some(U256::from(14)));
+    }
+
+    #[test]
+    fn mul_div_reports_overflow_and_division_by_zero() {
+        assert!(matches!(
+            mul_div(U256::MAX, U256::from(2), U256::from(2)),
+            Err(TokenSaleErrors::MathOverflow(_))
+        ));
+        assert!(matches!(
+            mul_div_up(U256::from(1), U256::from(1), U256::ZERO),
+            Err(TokenSaleErrors::DivisionByZero(_))
+        ));
+    }
+
+    #[test]
+    fn scale_moves_between_decimals() {
+        assert_eq!(
+            scale(U256::from(1_500_000), 6, 18).ok(),
+            Some(wad(3) / U256::from(2))
+        );
+        assert_eq!(
+            scale(U256::from(1_999_999_999_999u64), 18, 6).ok(),
+            Some(U256::from(1))
+        );
+        assert!(pow10(78).is_err());
+    }
+
+    #[test]
+    fn sqrt_up_rounds_up() {
+        assert_eq!(sqrt_up(U256::ZERO), U256::ZERO);
+        assert_eq!(sqrt_up(U256::from(1)), U256::from(1));
+        assert_eq!(sqrt_up(U256::from(2)), U256::from(2));
+        assert_eq!(sqrt_up(U256::from(16)), U256::from(4));
+        assert_eq!(sqrt_up(U256::from(17)), U256::from(5));
+        assert_eq!(sqrt_up(U256::MAX), U256::from(1) << 128);
+
+        for x in (1..2000u64).map(|x| U256::from(x * x * 7919)) {
+            let root = sqrt_up(x);
+            assert!(root * root >= x);
+            assert!((root - U256::from(1)) * (root - U256::from(1)) < x);
+        }
+    }
+
+    #[test]
+    fn exp_wad_brackets_e_to_the_x() {
+        assert_eq!(exp_wad(U256::ZERO).ok(), Some(WAD));
+        assert_eq!(exp_wad_up(U256::ZERO).ok(), Some(WAD));
+
+        // e = 2.718281828459045235360...
+        assert_eq!(
+            exp_wad(WAD).ok(),
+            Some(U256::from(2_718_281_828_459_045_235u64))
+        );
+        assert_eq!(
+            exp_wad_up(WAD).ok(),
+            Some(U256::from(2_718_281_828_459_045_236u64))
+        );
+
+        // e^ln(2) = 1.999999999999999999165... for the 18-decimal ln(2).
+        let ln2 = U256::from(693_147_180_559_945_309u64);
+        assert_eq!(exp_wad(ln2).ok(), Some(WAD * U256::from(2) - U256::from(1)));
+        assert_eq!(exp_wad_up(ln2).ok(), Some(WAD * U256::from(2)));
+
+        // e^42 = 1739274941520501047.394681303611235226147... * 1e18
+        let e42 = U256::from(1_739_274_941_520_501_047_394_681_303_611_235_226u128);
+        let low = exp_wad(wad(42)).ok().unwrap();
+        let high = exp_wad_up(wad(42)).ok().unwrap();
+        assert!(low <= e42 && e42 < high);
+        assert!(high - low < U256::from(1000));
+    }
+
+    #[test]
+    fn exp_wad_reports_overflow() {
+        assert!(exp_wad(wad(134)).is_ok());
+        assert!(matches!(
+            exp_wad(wad(136)),
+            Err(TokenSaleErrors::MathOverflow(_))
+        ));
+        assert!(exp_wad_up(wad(136)).is_err());
+        assert!(exp_wad(U256::MAX).is_err());
+    }
+
+    #[test]
+    fn ln_wad_rounds_down() {
+        assert_eq!(ln_wad(WAD).ok(), Some(U256::ZERO));
+
+        // ln(2) = 0.693147180559945309417...
+        assert_eq!(
+            ln_wad(wad(2)).ok(),
+            Some(U256::from(693_147_180_559_945_309u64))
+        );
+
+        // ln(10) = 2.302585092994045684017...
+        assert_eq!(
+            ln_wad(wad(10)).ok(),
+            Some(U256::from(2_302_585_092_994_045_684u64))
+        );
+
+        assert!(matches!(
+            ln_wad(WAD - U256::from(1)),
+            Err(TokenSaleErrors::MathOverflow(_))
+        ));
+    }
+
+    #[test]
+    fn exp_wad_undoes_ln_wad() {
+        for y in [
+            WAD + U256::from(1),
+            wad(3) / U256::from(2),
+            wad(2),
+            wad(1_000),
+            wad(123_456_789),
+            U256::from(1) << 200,
+        ] {
+            let x = exp_wad(ln_wad(y).ok().unwrap()).ok().unwrap();
+            let error = if x > y { x - y } else { y - x };
+
+            // ln_wad drops below 1e-18 in the exponent.
+            assert!(error <= y / U256::from(1_000_000_000_000_000u64) + U256::from(2));
+            assert!(exp_wad_up(ln_wad(y).ok().unwrap()).ok().unwrap() >= x);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Merkle proofs in the format of OpenZeppelin's `MerkleProof`: every pair
+//! of nodes is sorted before it is hashed, so proofs carry no path bits.
+
+use stylus_sdk::{alloy_primitives::B256, crypto};
+
+pub fn verify(proof: &[B256], root: B256, leaf: B256) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        let (a, b) = if node <= *sibling {
+            (node, *sibling)
+        } else {
+            (*sibling, node)
+        };
+        crypto::keccak([a.as_slice(), b.as_slice()].concat())
+    });
+
+    computed == root
+}
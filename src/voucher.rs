@@ -0,0 +1,78 @@
+//! EIP-712 purchase vouchers signed off-chain by the sale's compliance
+//! signer, `Voucher(address buyer,uint256 maxAmount,uint256 expiry,uint256 nonce)`
+//! under the `TokenSale` version `1` domain.
+
+use alloc::vec::Vec;
+use alloy_sol_types::SolValue;
+use stylus_sdk::{
+    alloy_primitives::{Address, B256, U256},
+    block,
+    call::{self, Call},
+    contract, crypto,
+};
+
+/// Half the secp256k1 group order. Signatures with a larger `s` are the
+/// malleable twins of valid ones and are rejected, as in OpenZeppelin's
+/// `ECDSA`.
+const SECP256K1N_HALF: U256 = U256::from_limbs([
+    16134479119472337056,
+    6725966010171805725,
+    18446744073709551615,
+    9223372036854775807,
+]);
+
+const ECRECOVER: Address = Address::with_last_byte(1);
+
+fn domain_separator() -> B256 {
+    crypto::keccak(
+        (
+            crypto::keccak(
+                "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)",
+            ),
+            crypto::keccak("TokenSale"),
+            crypto::keccak("1"),
+            U256::from(block::chainid()),
+            contract::address(),
+        )
+            .abi_encode(),
+    )
+}
+
+/// The EIP-712 digest the signer signs for a voucher.
+pub fn digest(buyer: Address, max_amount: U256, expiry: U256, nonce: U256) -> B256 {
+    let struct_hash = crypto::keccak(
+        (
+            crypto::keccak("Voucher(address buyer,uint256 maxAmount,uint256 expiry,uint256 nonce)"),
+            buyer,
+            max_amount,
+            expiry,
+            nonce,
+        )
+            .abi_encode(),
+    );
+
+    let mut data = Vec::with_capacity(66);
+    data.extend_from_slice(b"\x19\x01");
+    data.extend_from_slice(domain_separator().as_slice());
+    data.extend_from_slice(struct_hash.as_slice());
+
+    crypto::keccak(data)
+}
+
+/// Recovers the signer of `digest` through the `ecrecover` precompile, or
+/// `None` for an invalid signature.
+pub fn recover(digest: B256, v: u8, r: B256, s: B256) -> Option<Address> {
+    if U256::from_be_bytes(s.0) > SECP256K1N_HALF {
+        return None;
+    }
+
+    let input = (digest, U256::from(v), r, s).abi_encode();
+    let output = call::static_call(Call::new(), ECRECOVER, &input).ok()?;
+
+    if output.len() != 32 {
+        return None;
+    }
+
+    let signer = Address::from_slice(&output[12..]);
+    (!signer.is_zero()).then_some(signer)
+}
@@ -4,16 +4,232 @@ use e2e::alloy::sol;
 sol!(
     #[sol(rpc)]
     interface ITokenSale {
-    function initialise(address admin, address token, address oracle, uint256 total_supply, uint256 sale_end, uint256 initial_price, address[] memory supported_tokens) external;
+    function initialise(address admin, address token, address oracle, uint256 total_supply, uint256 sale_end, uint256 initial_price, uint256 soft_cap, uint8 pricing_mode, uint256 curve_rate, address[] memory supported_tokens, uint8[] memory price_indices) external;
 
-    function buyToken(uint256 amount, address token_in, uint8 price_index) external;
+    function buyToken(uint256 amount, address token_in, bool allow_partial) external;
+
+    function buyTokenExact(uint256 amount, address token_in, uint256 min_amount_out, uint256 deadline) external;
+
+    function buyExactTokens(uint256 amount_out, address token_in, uint256 max_amount_in) external;
+
+    function buyTokenWithPermit(uint256 amount, address token_in, uint256 deadline, uint8 v, bytes32 r, bytes32 s) external;
+
+    function buyTokenPermit2(uint256 amount, address token_in, bool allow_partial, uint256 nonce, uint256 deadline, bytes calldata signature) external;
+
+    function setPermit2(address permit2) external;
+
+    function buyWithNative() external payable;
 
     function withdraw(address token_addr) external;
 
+    function refund(address token_in) external;
+
+    function addSupportedToken(address token, address oracle, uint8 price_index) external;
+
+    function setPriceSource(address token, address oracle, uint8 price_index) external;
+
+    function setChainlinkPriceSource(address token, address feed, uint256 heartbeat) external;
+
+    function setSequencerUptimeFeed(address feed) external;
+
+    function priceSource(address token) external view returns (address oracle, uint8 priceIndex);
+
+    function removeSupportedToken(address token) external;
+
+    function supportedTokens() external view returns (address[] memory tokens);
+
+    function reclaimSaleTokens() external;
+
+    function configureVesting(uint256 cliff, uint256 duration) external;
+
+    function claim() external;
+
+    function vestedOf(address account) external view returns (uint256 vested);
+
+    function claimableOf(address account) external view returns (uint256 claimable);
+
     function isInitialised() external view returns (bool isInitialised);
 
+    function raisedUsd() external view returns (uint256 raisedUsd);
+
+    function setPriceTiers(uint256[] memory thresholds, uint256[] memory prices) external;
+
+    function priceTiers() external view returns (uint256[] memory thresholds, uint256[] memory prices);
+
+    function currentPrice() external view returns (uint256 currentPrice);
+
+    function configureDutchAuction(uint256 start_price, uint256 floor_price, uint256 start_time) external;
+
+    function currentAuctionPrice() external view returns (uint256 price);
+
+    function setSaleMode(uint8 mode) external;
+
+    function placeBid(uint256 amount, address token_in, uint256 max_price, uint256 hint) external;
+
+    function bidLevelHint(uint256 max_price) external view returns (uint256 hint);
+
+    function settle(uint32 max_steps) external;
+
+    function buyTokenWhitelisted(uint256 amount, address token_in, uint256 max_allocation, bytes32[] memory proof) external;
+
+    function setMerkleRoot(bytes32 root) external;
+
+    function whitelistUsed(address account) external view returns (uint256 used);
+
+    function buyTokenWithVoucher(uint256 amount, address token_in, uint256 max_amount, uint256 expiry, uint256 nonce, uint8 v, bytes32 r, bytes32 s) external;
+
+    function setVoucherSigner(address signer) external;
+
+    function voucherDigest(address buyer, uint256 max_amount, uint256 expiry, uint256 nonce) external view returns (bytes32 digest);
+
+    function isNonceUsed(address buyer, uint256 nonce) external view returns (bool used);
+
+    function setContributionLimits(uint256 min_usd, uint256 max_usd) external;
+
+    function remainingAllocation(address account) external view returns (uint256 remaining);
+
+    function setSaleStart(uint256 sale_start) external;
+
+    function setPhases(uint256[] memory starts, uint256[] memory ends, uint256[] memory prices, uint256[] memory wallet_caps) external;
+
+    function phases() external view returns (uint256[] memory starts, uint256[] memory ends, uint256[] memory prices, uint256[] memory walletCaps);
+
+    function currentPhase() external view returns (uint256 phase);
+
+    function finalize() external;
+
+    function refundExcess(address token_in) external;
+
+    function clearingPrice() external view returns (uint256 price);
+    function isSettled() external view returns (bool settled);
+
+    function calculateAmountOut(uint256 amount, address token_in) external view returns (uint256 amountOut);
+
+    function quoteBuy(uint256 amount, address token_in) external view returns (uint256 amountOut, uint256 effectivePrice, uint256 postTradePrice, uint256 remainingSupply);
+
+    struct SaleState {
+        address admin;
+        address token;
+        address oracle;
+        uint256 totalSupply;
+        uint256 tokensSold;
+        uint256 raisedUsd;
+        uint256 softCap;
+        uint256 saleStart;
+        uint256 saleEnd;
+        uint256 currentPrice;
+    }
+
+    function saleState() external view returns (SaleState memory state);
+
+    function quoteAmountIn(uint256 amount_out, address token_in) external view returns (uint256 amountIn);
+
+    function quoteCost(uint256 amount_out) external view returns (uint256 cost);
+
     error NotOwner();
 
     error ZeroAddressNotAllowed();
+
+    error NotAdmin();
+
+    error SaleEnded();
+
+    error EndtimeInPast();
+
+    error SaleAlreadyStarted();
+
+    error NothingToClaim();
+
+    error SaleNotEnded();
+
+    error SoftCapReached();
+
+    error SoftCapNotReached();
+
+    error NothingToRefund();
+
+    error SoldOut();
+
+    error UnsupportedToken();
+
+    error LengthMismatch();
+
+    error StalePrice();
+
+    error InvalidPrice();
+
+    error SequencerDown();
+
+    error GracePeriodNotOver();
+
+    error MathOverflow();
+
+    error DivisionByZero();
+
+    error TransferFailed(address token);
+
+    error TransferFromFailed(address token);
+
+    error OracleCallFailed(address oracle);
+
+    error DecimalsCallFailed(address token);
+
+    error InvalidPriceTiers();
+
+    error InvalidPricingMode();
+
+    error InvalidAuctionConfig();
+
+    error InvalidSaleMode();
+
+    error BidTooLow();
+
+    error NotSettled();
+
+    error AlreadySettled();
+
+    error SaleNotStarted();
+
+    error PhaseClosed();
+
+    error InvalidSchedule();
+
+    error WalletCapExceeded();
+
+    error WhitelistOnly();
+
+    error InvalidProof();
+
+    error AllocationExceeded();
+
+    error VoucherExpired();
+
+    error VoucherUsed();
+
+    error InvalidSignature();
+
+    error InvalidContributionLimits();
+
+    error BelowMinimum();
+
+    error AboveMaximum();
+
+    error PermitFailed(address token);
+
+    error SlippageExceeded(uint256 amount_out, uint256 min_amount_out);
+
+    error DeadlineExpired();
+
+    error ExcessiveAmountIn(uint256 amount_in, uint256 max_amount_in);
+
+    error ZeroAmount();
+
+    error VoucherRequired();
+
+    error ConflictingGates();
+
+    error InvalidBidHint();
+
+    error FeatureDisabled();
 }
 );
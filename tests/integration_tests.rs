@@ -2,7 +2,12 @@ mod abi;
 mod mocks;
 
 use e2e::{
-    alloy::{primitives::utils::parse_ether, providers::Provider},
+    alloy::{
+        self,
+        primitives::{hex, utils::parse_ether, Address, U256},
+        providers::Provider,
+        sol_types::SolError,
+    },
     eyre::Result,
     send, tokio, Account, ReceiptExt,
 };
@@ -10,13 +15,48 @@ use e2e::{
 const MINT_AMOUNT: &str = "10000000000";
 const ADMIN_TOTAL_SUPPLY: &str = "1000";
 
+#[cfg(any(feature = "allowlist", feature = "vouchers"))]
+use e2e::alloy::primitives::keccak256;
+#[cfg(feature = "permit2")]
+use e2e::alloy::primitives::Bytes;
+#[cfg(any(
+    feature = "allowlist",
+    feature = "vouchers",
+    feature = "permit",
+    feature = "permit2"
+))]
+use e2e::alloy::primitives::B256;
+#[cfg(feature = "chainlink")]
+use e2e::alloy::primitives::I256;
+
 use abi::ITokenSale;
 
+#[cfg(feature = "chainlink")]
+use mocks::aggregator::{self, AggregatorMock};
+#[cfg(feature = "permit")]
+use mocks::erc20_permit::{self, ERC20PermitMock};
+#[cfg(feature = "permit2")]
+use mocks::permit2::{self, Permit2Mock};
 use mocks::{
     erc20::{self, ERC20Mock},
+    erc20_taxed::{self, ERC20TaxedMock},
     oracle::{self},
 };
 
+/// Whether `result` failed with exactly the ABI-encoded custom error
+/// `expected`, arguments included.
+fn reverted_with<T>(result: Result<T, alloy::contract::Error>, expected: impl SolError) -> bool {
+    let Err(alloy::contract::Error::TransportError(err)) = result else {
+        return false;
+    };
+
+    err.as_error_resp()
+        .and_then(|payload| payload.data.as_ref())
+        .is_some_and(|data| {
+            data.get().trim_matches('"') == hex::encode_prefixed(expected.abi_encode())
+        })
+}
+
 #[e2e::test]
 async fn accounts_are_funded(alice: Account) -> Result<()> {
     let balance = alice.wallet.get_balance(alice.address()).await?;
@@ -63,7 +103,11 @@ async fn it_can_be_initialised(alice: Account, bob: Account) -> Result<()> {
         parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
         parse_ether("1").unwrap(),
         parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
         vec![usdc_address],
+        vec![1],
     ))
     .unwrap();
 
@@ -117,7 +161,11 @@ async fn it_can_be_buy_using_usdc(alice: Account, bob: Account) -> Result<()> {
         parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
         parse_ether("1").unwrap(),
         parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
         vec![usdc_address],
+        vec![1],
     ))
     .unwrap();
 
@@ -126,10 +174,15 @@ async fn it_can_be_buy_using_usdc(alice: Account, bob: Account) -> Result<()> {
 
     let _ = send!(usdc_contract_bob.approve(contract_addr, parse_ether("10").unwrap())).unwrap();
 
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(U256::ZERO, usdc_address, false)),
+        ITokenSale::ZeroAmount {}
+    ));
+
     let bob_usdc_bal_before = usdc_contract.balanceOf(bob.address()).call().await?;
     let bob_token_bal_before = token_contract.balanceOf(bob.address()).call().await?;
 
-    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, 1)).unwrap();
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)).unwrap();
 
     let bob_token_bal_after = token_contract.balanceOf(bob.address()).call().await?;
     let bob_usdc_bal_after = usdc_contract.balanceOf(bob.address()).call().await?;
@@ -162,7 +215,11 @@ async fn admin_can_withdraw(alice: Account, bob: Account) -> Result<()> {
         parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
         parse_ether("1").unwrap(),
         parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
         vec![usdc_address],
+        vec![1],
     ))
     .unwrap();
 
@@ -171,7 +228,7 @@ async fn admin_can_withdraw(alice: Account, bob: Account) -> Result<()> {
 
     let _ = send!(usdc_contract_bob.approve(contract_addr, parse_ether("10").unwrap())).unwrap();
 
-    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, 1)).unwrap();
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)).unwrap();
 
     let alice_usdc_bal_before = usdc_contract.balanceOf(alice.address()).call().await?;
 
@@ -183,3 +240,2040 @@ async fn admin_can_withdraw(alice: Account, bob: Account) -> Result<()> {
 
     Ok(())
 }
+
+#[e2e::test]
+async fn vesting_holds_purchased_tokens(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let usdc_contract = ERC20Mock::new(usdc_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(usdc_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![usdc_address],
+        vec![1],
+    ))
+    .unwrap();
+
+    let _ = send!(contract.configureVesting(U256::from(60), U256::from(600))).unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let usdc_contract_bob = ERC20Mock::new(usdc_address, &bob.wallet);
+
+    let _ = send!(usdc_contract_bob.approve(contract_addr, parse_ether("10").unwrap())).unwrap();
+
+    let bob_token_bal_before = token_contract.balanceOf(bob.address()).call().await?;
+
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)).unwrap();
+
+    let bob_token_bal_after = token_contract.balanceOf(bob.address()).call().await?;
+
+    assert_eq!(bob_token_bal_after.balance, bob_token_bal_before.balance);
+
+    let ITokenSale::vestedOfReturn { vested } = contract.vestedOf(bob.address()).call().await?;
+    assert_eq!(vested, U256::ZERO);
+
+    assert!(reverted_with(
+        send!(contract_bob.claim()),
+        ITokenSale::NothingToClaim {}
+    ));
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn withdraw_is_blocked_below_soft_cap(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let usdc_contract = ERC20Mock::new(usdc_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(usdc_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("100").unwrap(),
+        0,
+        U256::ZERO,
+        vec![usdc_address],
+        vec![1],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let usdc_contract_bob = ERC20Mock::new(usdc_address, &bob.wallet);
+
+    let _ = send!(usdc_contract_bob.approve(contract_addr, parse_ether("10").unwrap())).unwrap();
+
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)).unwrap();
+
+    let ITokenSale::raisedUsdReturn { raisedUsd } = contract.raisedUsd().call().await?;
+    assert_eq!(raisedUsd, parse_ether("9.9").unwrap());
+
+    assert!(reverted_with(
+        send!(contract.withdraw(usdc_address)),
+        ITokenSale::SoftCapNotReached {}
+    ));
+
+    assert!(reverted_with(
+        send!(contract_bob.refund(usdc_address)),
+        ITokenSale::SaleNotEnded {}
+    ));
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn buy_fills_partially_at_the_hard_cap(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let usdc_contract = ERC20Mock::new(usdc_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(usdc_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![usdc_address],
+        vec![1],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let usdc_contract_bob = ERC20Mock::new(usdc_address, &bob.wallet);
+
+    let _ = send!(usdc_contract_bob.approve(contract_addr, parse_ether("2000").unwrap())).unwrap();
+
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("2000").unwrap(), usdc_address, false)),
+        ITokenSale::SoldOut {}
+    ));
+
+    let bob_usdc_bal_before = usdc_contract.balanceOf(bob.address()).call().await?;
+
+    let _ = send!(contract_bob.buyToken(parse_ether("2000").unwrap(), usdc_address, true)).unwrap();
+
+    let bob_usdc_bal_after = usdc_contract.balanceOf(bob.address()).call().await?;
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+
+    assert_eq!(
+        bob_token_bal.balance,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()
+    );
+    assert!(
+        bob_usdc_bal_before.balance - bob_usdc_bal_after.balance < parse_ether("2000").unwrap()
+    );
+
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, true)),
+        ITokenSale::SoldOut {}
+    ));
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn it_can_be_bought_with_native_currency(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![Address::ZERO],
+        vec![0],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+
+    let _ = send!(contract_bob
+        .buyWithNative()
+        .value(parse_ether("1").unwrap()))
+    .unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("0.5").unwrap());
+
+    let contract_bal = alice.wallet.get_balance(contract_addr).await?;
+    assert_eq!(contract_bal, parse_ether("1").unwrap());
+
+    let _ = send!(contract.withdraw(Address::ZERO)).unwrap();
+
+    let contract_bal = alice.wallet.get_balance(contract_addr).await?;
+    assert_eq!(contract_bal, U256::ZERO);
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn only_supported_tokens_can_be_used(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let usdc_contract = ERC20Mock::new(usdc_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(usdc_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![],
+        vec![],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let usdc_contract_bob = ERC20Mock::new(usdc_address, &bob.wallet);
+
+    let _ = send!(usdc_contract_bob.approve(contract_addr, parse_ether("20").unwrap())).unwrap();
+
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)),
+        ITokenSale::UnsupportedToken {}
+    ));
+
+    let _ = send!(contract.addSupportedToken(usdc_address, oracle_address, 1)).unwrap();
+
+    let ITokenSale::supportedTokensReturn { tokens } = contract.supportedTokens().call().await?;
+    assert_eq!(tokens, vec![usdc_address]);
+
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)).unwrap();
+
+    let _ = send!(contract.removeSupportedToken(usdc_address)).unwrap();
+
+    let ITokenSale::supportedTokensReturn { tokens } = contract.supportedTokens().call().await?;
+    assert!(tokens.is_empty());
+
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)),
+        ITokenSale::UnsupportedToken {}
+    ));
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn each_token_is_priced_by_its_own_source(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![usdc_address, arb_address],
+        vec![1, 0],
+    ))
+    .unwrap();
+
+    let ITokenSale::calculateAmountOutReturn { amountOut } = contract
+        .calculateAmountOut(parse_ether("10").unwrap(), usdc_address)
+        .call()
+        .await?;
+    assert_eq!(amountOut, parse_ether("9.9").unwrap());
+
+    let ITokenSale::calculateAmountOutReturn { amountOut } = contract
+        .calculateAmountOut(parse_ether("10").unwrap(), arb_address)
+        .call()
+        .await?;
+    assert_eq!(amountOut, parse_ether("5").unwrap());
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    assert!(reverted_with(
+        send!(contract_bob.setPriceSource(arb_address, oracle_address, 1)),
+        ITokenSale::NotAdmin {}
+    ));
+
+    let _ = send!(contract.setPriceSource(arb_address, oracle_address, 1)).unwrap();
+
+    let ITokenSale::priceSourceReturn { oracle, priceIndex } =
+        contract.priceSource(arb_address).call().await?;
+    assert_eq!(oracle, oracle_address);
+    assert_eq!(priceIndex, 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "chainlink")]
+#[e2e::test]
+async fn chainlink_prices_are_normalized_and_checked(alice: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let weth_address = erc20::deploy(&alice.wallet).await?;
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+    let feed_address = aggregator::deploy(&alice.wallet).await?;
+    let feed_contract = AggregatorMock::new(feed_address, &alice.wallet);
+    let sequencer_address = aggregator::deploy(&alice.wallet).await?;
+    let sequencer_contract = AggregatorMock::new(sequencer_address, &alice.wallet);
+
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![weth_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    // 2000 USD with the feed's 8 decimals.
+    let _ = send!(feed_contract.setAnswer(I256::from_raw(U256::from(200_000_000_000u64)))).unwrap();
+    let _ = send!(contract.setChainlinkPriceSource(weth_address, feed_address, U256::from(3600)))
+        .unwrap();
+
+    let ITokenSale::calculateAmountOutReturn { amountOut } = contract
+        .calculateAmountOut(parse_ether("1").unwrap(), weth_address)
+        .call()
+        .await?;
+    assert_eq!(amountOut, parse_ether("2000").unwrap());
+
+    let _ = send!(sequencer_contract.setAnswer(I256::from_raw(U256::from(1)))).unwrap();
+    let _ = send!(contract.setSequencerUptimeFeed(sequencer_address)).unwrap();
+
+    assert!(reverted_with(
+        contract
+            .calculateAmountOut(parse_ether("1").unwrap(), weth_address)
+            .call()
+            .await,
+        ITokenSale::SequencerDown {}
+    ));
+
+    let _ = send!(contract.setSequencerUptimeFeed(Address::ZERO)).unwrap();
+    let _ = send!(feed_contract.setUpdatedAt(U256::from(1))).unwrap();
+
+    assert!(reverted_with(
+        contract
+            .calculateAmountOut(parse_ether("1").unwrap(), weth_address)
+            .call()
+            .await,
+        ITokenSale::StalePrice {}
+    ));
+
+    Ok(())
+}
+
+/// Initialises a sale of a `sale_decimals` token accepting a
+/// `payment_decimals` token and quotes `amount` of the latter.
+async fn quote_across_decimals(
+    alice: &Account,
+    sale_decimals: u8,
+    payment_decimals: u8,
+    price_index: u8,
+    amount: U256,
+) -> Result<U256> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy_with_decimals(&alice.wallet, sale_decimals).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let payment_address = erc20::deploy_with_decimals(&alice.wallet, payment_decimals).await?;
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let total_supply = U256::from(1000) * U256::from(10).pow(U256::from(sale_decimals));
+
+    let _ = send!(token_contract.mint(alice.address(), total_supply));
+    let _ = send!(token_contract.approve(contract_addr, total_supply));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        total_supply,
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![payment_address],
+        vec![price_index],
+    ))
+    .unwrap();
+
+    let ITokenSale::calculateAmountOutReturn { amountOut } = contract
+        .calculateAmountOut(amount, payment_address)
+        .call()
+        .await?;
+
+    Ok(amountOut)
+}
+
+#[e2e::test]
+async fn amounts_are_scaled_by_token_decimals(alice: Account) -> Result<()> {
+    // 10 six-decimal USDC at 0.99 USD buys 9.9 eighteen-decimal tokens.
+    let amount_out = quote_across_decimals(&alice, 18, 6, 1, U256::from(10_000_000)).await?;
+    assert_eq!(amount_out, parse_ether("9.9").unwrap());
+
+    // 10 eight-decimal ARB at 0.5 USD buys 5 six-decimal tokens.
+    let amount_out = quote_across_decimals(&alice, 6, 8, 0, U256::from(1_000_000_000)).await?;
+    assert_eq!(amount_out, U256::from(5_000_000));
+
+    // 10 eighteen-decimal USDC at 0.99 USD buys 9.9 eight-decimal tokens.
+    let amount_out = quote_across_decimals(&alice, 8, 18, 1, parse_ether("10").unwrap()).await?;
+    assert_eq!(amount_out, U256::from(990_000_000));
+
+    // 10 six-decimal USDC at 0.99 USD buys 9.9 six-decimal tokens.
+    let amount_out = quote_across_decimals(&alice, 6, 6, 1, U256::from(10_000_000)).await?;
+    assert_eq!(amount_out, U256::from(9_900_000));
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn zero_price_reverts_instead_of_panicking(alice: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![usdc_address],
+        vec![1],
+    ))
+    .unwrap();
+
+    assert!(reverted_with(
+        contract
+            .calculateAmountOut(parse_ether("10").unwrap(), usdc_address)
+            .call()
+            .await,
+        ITokenSale::DivisionByZero {}
+    ));
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn buy_without_allowance_reverts(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let usdc_contract = ERC20Mock::new(usdc_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(usdc_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![usdc_address],
+        vec![1],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)),
+        ITokenSale::TransferFromFailed {
+            token: usdc_address
+        }
+    ));
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, U256::ZERO);
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn price_follows_the_tier_schedule(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let usdc_contract = ERC20Mock::new(usdc_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(usdc_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![usdc_address],
+        vec![1],
+    ))
+    .unwrap();
+
+    assert!(reverted_with(
+        send!(contract.setPriceTiers(
+            vec![parse_ether("100").unwrap(), parse_ether("10").unwrap()],
+            vec![parse_ether("2").unwrap(), parse_ether("3").unwrap()],
+        )),
+        ITokenSale::InvalidPriceTiers {}
+    ));
+
+    let _ = send!(contract.setPriceTiers(
+        vec![parse_ether("10").unwrap(), parse_ether("100").unwrap()],
+        vec![parse_ether("2").unwrap(), parse_ether("3").unwrap()],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let usdc_contract_bob = ERC20Mock::new(usdc_address, &bob.wallet);
+
+    let _ = send!(usdc_contract_bob.approve(contract_addr, parse_ether("30").unwrap())).unwrap();
+
+    // 9.9 tokens sold: still below the first threshold.
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)).unwrap();
+
+    let ITokenSale::currentPriceReturn { currentPrice } = contract.currentPrice().call().await?;
+    assert_eq!(currentPrice, parse_ether("1").unwrap());
+
+    // Crossing the threshold, the last 0.1 tokens of the base tier cost 1 USD
+    // each and the rest 2 USD.
+    let ITokenSale::quoteCostReturn { cost } = contract
+        .quoteCost(parse_ether("10.1").unwrap())
+        .call()
+        .await?;
+    assert_eq!(cost, parse_ether("20.1").unwrap());
+
+    // 19.8 USD buys those 0.1 tokens for 0.1 USD and 9.85 more at 2 USD.
+    let _ = send!(contract_bob.buyToken(parse_ether("20").unwrap(), usdc_address, false)).unwrap();
+
+    let ITokenSale::currentPriceReturn { currentPrice } = contract.currentPrice().call().await?;
+    assert_eq!(currentPrice, parse_ether("2").unwrap());
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("19.85").unwrap());
+
+    Ok(())
+}
+
+#[cfg(feature = "bonding-curves")]
+#[e2e::test]
+async fn linear_curve_integrates_the_price(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    // Exponential curves need a growth rate.
+    assert!(reverted_with(
+        send!(contract.initialise(
+            alice.address(),
+            token_address,
+            oracle_address,
+            parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+            parse_ether("1").unwrap(),
+            parse_ether("1").unwrap(),
+            U256::ZERO,
+            2,
+            U256::ZERO,
+            vec![arb_address],
+            vec![0],
+        )),
+        ITokenSale::InvalidPricingMode {}
+    ));
+
+    // The price starts at 1 USD and rises by 0.02 USD per token sold.
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        1,
+        parse_ether("0.02").unwrap(),
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    // 10 tokens cost 10 * 1 + 0.02 * 10^2 / 2 = 11 USD, i.e. 22 ARB at 0.5 USD.
+    let ITokenSale::quoteCostReturn { cost } = contract
+        .quoteCost(parse_ether("10").unwrap())
+        .call()
+        .await?;
+    assert_eq!(cost, parse_ether("11").unwrap());
+
+    let ITokenSale::calculateAmountOutReturn { amountOut } = contract
+        .calculateAmountOut(parse_ether("22").unwrap(), arb_address)
+        .call()
+        .await?;
+    assert_eq!(amountOut, parse_ether("10").unwrap());
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("22").unwrap())).unwrap();
+    let _ = send!(contract_bob.buyToken(parse_ether("22").unwrap(), arb_address, false)).unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("10").unwrap());
+
+    let ITokenSale::currentPriceReturn { currentPrice } = contract.currentPrice().call().await?;
+    assert_eq!(currentPrice, parse_ether("1.2").unwrap());
+
+    Ok(())
+}
+
+async fn latest_timestamp(account: &Account) -> Result<u64> {
+    let number = account.wallet.get_block_number().await?;
+    let block = account
+        .wallet
+        .get_block_by_number(number.into(), false)
+        .await?
+        .unwrap();
+
+    Ok(block.header.timestamp)
+}
+
+/// Waits until the chain clock is past `timestamp` and mines a block on top,
+/// since the dev node cannot warp time.
+async fn wait_past(account: &Account, timestamp: u64) -> Result<()> {
+    let now = latest_timestamp(account).await?;
+    if now <= timestamp {
+        std::thread::sleep(std::time::Duration::from_secs(timestamp - now + 1));
+    }
+
+    erc20::deploy(&account.wallet).await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "dutch-auction")]
+#[e2e::test]
+async fn dutch_auction_price_decays_to_the_floor(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let usdc_address = erc20::deploy(&alice.wallet).await?;
+    let usdc_contract = ERC20Mock::new(usdc_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(usdc_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let now = latest_timestamp(&alice).await?;
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        U256::from(now + 10),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![usdc_address],
+        vec![1],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    assert!(reverted_with(
+        send!(contract_bob.configureDutchAuction(
+            parse_ether("2").unwrap(),
+            parse_ether("1").unwrap(),
+            U256::from(now + 5),
+        )),
+        ITokenSale::NotAdmin {}
+    ));
+
+    // The floor cannot be above the start price.
+    assert!(reverted_with(
+        send!(contract.configureDutchAuction(
+            parse_ether("1").unwrap(),
+            parse_ether("2").unwrap(),
+            U256::from(now + 5),
+        )),
+        ITokenSale::InvalidAuctionConfig {}
+    ));
+
+    // Before the start time the price holds at the start price.
+    let _ = send!(contract.configureDutchAuction(
+        parse_ether("2").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::from(now + 5),
+    ))
+    .unwrap();
+
+    let ITokenSale::currentAuctionPriceReturn { price } =
+        contract.currentAuctionPrice().call().await?;
+    assert_eq!(price, parse_ether("2").unwrap());
+
+    // Starting 10 seconds ago, the price is somewhere inside the window.
+    let _ = send!(contract.configureDutchAuction(
+        parse_ether("2").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::from(now - 10),
+    ))
+    .unwrap();
+
+    let ITokenSale::currentAuctionPriceReturn { price } =
+        contract.currentAuctionPrice().call().await?;
+    assert!(price < parse_ether("2").unwrap() && price > parse_ether("1").unwrap());
+
+    let usdc_contract_bob = ERC20Mock::new(usdc_address, &bob.wallet);
+
+    let _ = send!(usdc_contract_bob.approve(contract_addr, parse_ether("10").unwrap())).unwrap();
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), usdc_address, false)).unwrap();
+
+    // 9.9 USD bought at a price between 1 and 2 USD.
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert!(bob_token_bal.balance > parse_ether("4.95").unwrap());
+    assert!(bob_token_bal.balance < parse_ether("9.9").unwrap());
+
+    wait_past(&alice, now + 10).await?;
+
+    let ITokenSale::currentAuctionPriceReturn { price } =
+        contract.currentAuctionPrice().call().await?;
+    assert_eq!(price, parse_ether("1").unwrap());
+
+    Ok(())
+}
+
+#[cfg(feature = "batch-auction")]
+#[e2e::test]
+async fn batch_auction_clears_at_a_uniform_price(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(arb_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+    let _ = send!(arb_contract.approve(contract_addr, parse_ether("2000").unwrap()));
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("1000").unwrap())).unwrap();
+
+    let now = latest_timestamp(&alice).await?;
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        U256::from(now + 20),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let _ = send!(contract.setSaleMode(1)).unwrap();
+
+    // Bids below the 1 USD reserve are rejected.
+    assert!(reverted_with(
+        send!(contract_bob.placeBid(
+            parse_ether("1000").unwrap(),
+            arb_address,
+            parse_ether("0.5").unwrap(),
+            U256::ZERO,
+        )),
+        ITokenSale::BidTooLow {}
+    ));
+
+    // So are bids worth less than 1 USD.
+    assert!(reverted_with(
+        send!(contract_bob.placeBid(
+            parse_ether("1").unwrap(),
+            arb_address,
+            parse_ether("2").unwrap(),
+            U256::ZERO,
+        )),
+        ITokenSale::BelowMinimum {}
+    ));
+
+    // Bob bids 500 USD at any price, alice 1000 USD at no more than 1 USD.
+    let _ = send!(contract_bob.buyToken(parse_ether("1000").unwrap(), arb_address, false)).unwrap();
+
+    // A hint that is not a price level is rejected.
+    assert!(reverted_with(
+        send!(contract.placeBid(
+            parse_ether("2000").unwrap(),
+            arb_address,
+            parse_ether("1").unwrap(),
+            parse_ether("2").unwrap(),
+        )),
+        ITokenSale::InvalidBidHint {}
+    ));
+
+    let ITokenSale::bidLevelHintReturn { hint } = contract
+        .bidLevelHint(parse_ether("1").unwrap())
+        .call()
+        .await?;
+    assert_eq!(hint, U256::MAX);
+
+    let _ = send!(contract.placeBid(
+        parse_ether("2000").unwrap(),
+        arb_address,
+        parse_ether("1").unwrap(),
+        hint,
+    ))
+    .unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, U256::ZERO);
+
+    assert!(reverted_with(
+        send!(contract.settle(10)),
+        ITokenSale::SaleNotEnded {}
+    ));
+    assert!(reverted_with(
+        send!(contract_bob.claim()),
+        ITokenSale::NotSettled {}
+    ));
+
+    wait_past(&alice, now + 20).await?;
+
+    // Two price levels to clear, then two bids to book, one step at a time.
+    for _ in 0..3 {
+        let _ = send!(contract.settle(1)).unwrap();
+
+        let ITokenSale::isSettledReturn { settled } = contract.isSettled().call().await?;
+        assert!(!settled);
+        assert!(reverted_with(
+            send!(contract_bob.claim()),
+            ITokenSale::NotSettled {}
+        ));
+    }
+
+    let _ = send!(contract.settle(1)).unwrap();
+
+    let ITokenSale::isSettledReturn { settled } = contract.isSettled().call().await?;
+    assert!(settled);
+    assert!(reverted_with(
+        send!(contract.settle(1)),
+        ITokenSale::AlreadySettled {}
+    ));
+
+    // 1500 USD of demand for 1000 tokens at 1 USD: bob is filled in full and
+    // alice, bidding exactly the clearing price, for the remaining half.
+    let ITokenSale::clearingPriceReturn { price } = contract.clearingPrice().call().await?;
+    assert_eq!(price, parse_ether("1").unwrap());
+
+    let _ = send!(contract_bob.claim()).unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("500").unwrap());
+
+    let alice_token_bal_before = token_contract.balanceOf(alice.address()).call().await?;
+    let alice_arb_bal_before = arb_contract.balanceOf(alice.address()).call().await?;
+
+    let _ = send!(contract.claim()).unwrap();
+
+    let alice_token_bal_after = token_contract.balanceOf(alice.address()).call().await?;
+    let alice_arb_bal_after = arb_contract.balanceOf(alice.address()).call().await?;
+
+    assert_eq!(
+        alice_token_bal_after.balance - alice_token_bal_before.balance,
+        parse_ether("500").unwrap()
+    );
+    assert_eq!(
+        alice_arb_bal_after.balance - alice_arb_bal_before.balance,
+        parse_ether("1000").unwrap()
+    );
+
+    assert!(reverted_with(
+        send!(contract.claim()),
+        ITokenSale::NothingToClaim {}
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "overflow-sale")]
+#[e2e::test]
+async fn oversubscribed_sale_allocates_pro_rata(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(arb_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+    let _ = send!(arb_contract.approve(contract_addr, parse_ether("3000").unwrap()));
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("1000").unwrap())).unwrap();
+
+    let now = latest_timestamp(&alice).await?;
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        U256::from(now + 20),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let _ = send!(contract.setSaleMode(2)).unwrap();
+
+    // 2000 USD is contributed for 1000 USD worth of tokens.
+    let _ = send!(contract_bob.buyToken(parse_ether("1000").unwrap(), arb_address, false)).unwrap();
+    let _ = send!(contract.buyToken(parse_ether("3000").unwrap(), arb_address, false)).unwrap();
+
+    // Contributions sell nothing until `finalize()` but still lock the sale.
+    assert!(reverted_with(
+        send!(contract.configureVesting(U256::ZERO, U256::from(100))),
+        ITokenSale::SaleAlreadyStarted {}
+    ));
+
+    // Removing ARB stops new payments but not the refunds owed in it.
+    let _ = send!(contract.removeSupportedToken(arb_address)).unwrap();
+
+    assert!(reverted_with(
+        send!(contract.finalize()),
+        ITokenSale::SaleNotEnded {}
+    ));
+    assert!(reverted_with(
+        send!(contract_bob.refundExcess(arb_address)),
+        ITokenSale::NotSettled {}
+    ));
+
+    wait_past(&alice, now + 20).await?;
+
+    let _ = send!(contract.finalize()).unwrap();
+    assert!(reverted_with(
+        send!(contract.finalize()),
+        ITokenSale::AlreadySettled {}
+    ));
+
+    // Bob put in a quarter of the total: a quarter of the supply and half of
+    // his payment back.
+    let bob_arb_bal_before = arb_contract.balanceOf(bob.address()).call().await?;
+
+    let _ = send!(contract_bob.refundExcess(arb_address)).unwrap();
+    assert!(reverted_with(
+        send!(contract_bob.refundExcess(arb_address)),
+        ITokenSale::NothingToRefund {}
+    ));
+
+    let bob_arb_bal_after = arb_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(
+        bob_arb_bal_after.balance - bob_arb_bal_before.balance,
+        parse_ether("500").unwrap()
+    );
+
+    let _ = send!(contract_bob.claim()).unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("250").unwrap());
+
+    let alice_token_bal_before = token_contract.balanceOf(alice.address()).call().await?;
+
+    let _ = send!(contract.claim()).unwrap();
+
+    let alice_token_bal_after = token_contract.balanceOf(alice.address()).call().await?;
+    assert_eq!(
+        alice_token_bal_after.balance - alice_token_bal_before.balance,
+        parse_ether("750").unwrap()
+    );
+
+    // Only the spent half is left for the admin.
+    let _ = send!(contract.withdraw(arb_address)).unwrap();
+
+    let contract_arb_bal = arb_contract.balanceOf(contract_addr).call().await?;
+    assert_eq!(contract_arb_bal.balance, U256::ZERO);
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn purchases_follow_the_phase_schedule(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("20").unwrap())).unwrap();
+
+    let now = latest_timestamp(&alice).await?;
+
+    // Not open yet.
+    let _ = send!(contract.setSaleStart(U256::from(now + 1000))).unwrap();
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("1").unwrap(), arb_address, false)),
+        ITokenSale::SaleNotStarted {}
+    ));
+    let _ = send!(contract.setSaleStart(U256::ZERO)).unwrap();
+
+    // Overlapping phases are rejected.
+    assert!(reverted_with(
+        send!(contract.setPhases(
+            vec![U256::from(now - 100), U256::from(now)],
+            vec![U256::from(now + 20), U256::from(now + 2000)],
+            vec![parse_ether("0.5").unwrap(), parse_ether("2").unwrap()],
+            vec![parse_ether("10").unwrap(), U256::ZERO],
+        )),
+        ITokenSale::InvalidSchedule {}
+    ));
+
+    // A private round at 0.5 USD capped at 10 tokens per wallet, then a gap
+    // before the public round.
+    let _ = send!(contract.setPhases(
+        vec![U256::from(now - 100), U256::from(now + 1000)],
+        vec![U256::from(now + 20), U256::from(now + 2000)],
+        vec![parse_ether("0.5").unwrap(), parse_ether("2").unwrap()],
+        vec![parse_ether("10").unwrap(), U256::ZERO],
+    ))
+    .unwrap();
+
+    let ITokenSale::currentPhaseReturn { phase } = contract.currentPhase().call().await?;
+    assert_eq!(phase, U256::ZERO);
+
+    let ITokenSale::currentPriceReturn { currentPrice } = contract.currentPrice().call().await?;
+    assert_eq!(currentPrice, parse_ether("0.5").unwrap());
+
+    // 10 ARB at 0.5 USD buys the whole 10 token cap.
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), arb_address, false)).unwrap();
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("1").unwrap(), arb_address, false)),
+        ITokenSale::WalletCapExceeded {}
+    ));
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("10").unwrap());
+
+    wait_past(&alice, now + 20).await?;
+
+    assert!(reverted_with(
+        contract.currentPhase().call().await,
+        ITokenSale::PhaseClosed {}
+    ));
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("1").unwrap(), arb_address, false)),
+        ITokenSale::PhaseClosed {}
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "allowlist")]
+fn allowlist_leaf(account: Address, max_allocation: U256) -> B256 {
+    keccak256([account.as_slice(), &max_allocation.to_be_bytes::<32>()].concat())
+}
+
+#[cfg(feature = "allowlist")]
+#[e2e::test]
+async fn allowlisted_buyers_buy_up_to_their_allocation(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    // A two-leaf allowlist: bob may buy 10 tokens, alice 5.
+    let bob_allocation = parse_ether("10").unwrap();
+    let bob_leaf = allowlist_leaf(bob.address(), bob_allocation);
+    let alice_leaf = allowlist_leaf(alice.address(), parse_ether("5").unwrap());
+    let root = if bob_leaf <= alice_leaf {
+        keccak256([bob_leaf.as_slice(), alice_leaf.as_slice()].concat())
+    } else {
+        keccak256([alice_leaf.as_slice(), bob_leaf.as_slice()].concat())
+    };
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    assert!(reverted_with(
+        send!(contract_bob.setMerkleRoot(root)),
+        ITokenSale::NotAdmin {}
+    ));
+    let _ = send!(contract.setMerkleRoot(root)).unwrap();
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("30").unwrap())).unwrap();
+
+    // The public entrypoint is closed while the allowlist is set.
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("10").unwrap(), arb_address, false)),
+        ITokenSale::WhitelistOnly {}
+    ));
+
+    // Claiming a larger allocation than the one in the tree fails the proof.
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenWhitelisted(
+            parse_ether("10").unwrap(),
+            arb_address,
+            parse_ether("20").unwrap(),
+            vec![alice_leaf],
+        )),
+        ITokenSale::InvalidProof {}
+    ));
+
+    // 10 ARB at 0.5 USD buys 5 tokens, twice.
+    for _ in 0..2 {
+        let _ = send!(contract_bob.buyTokenWhitelisted(
+            parse_ether("10").unwrap(),
+            arb_address,
+            bob_allocation,
+            vec![alice_leaf],
+        ))
+        .unwrap();
+    }
+
+    let ITokenSale::whitelistUsedReturn { used } =
+        contract.whitelistUsed(bob.address()).call().await?;
+    assert_eq!(used, bob_allocation);
+
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenWhitelisted(
+            parse_ether("2").unwrap(),
+            arb_address,
+            bob_allocation,
+            vec![alice_leaf],
+        )),
+        ITokenSale::AllocationExceeded {}
+    ));
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, bob_allocation);
+
+    Ok(())
+}
+
+#[cfg(feature = "vouchers")]
+#[e2e::test]
+async fn vouchers_gate_purchases(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    // Alice acts as the compliance signer.
+    let _ = send!(contract.setVoucherSigner(alice.address())).unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("30").unwrap())).unwrap();
+
+    // Only voucher holders can buy, and the allowlist cannot be combined with
+    // vouchers.
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("10").unwrap(), arb_address, false)),
+        ITokenSale::VoucherRequired {}
+    ));
+    #[cfg(feature = "allowlist")]
+    assert!(reverted_with(
+        send!(contract.setMerkleRoot(keccak256("allowlist"))),
+        ITokenSale::ConflictingGates {}
+    ));
+
+    let max_amount = parse_ether("5").unwrap();
+    let expiry = U256::from(latest_timestamp(&alice).await? + 1000);
+    let nonce = U256::from(1);
+
+    let ITokenSale::voucherDigestReturn { digest } = contract
+        .voucherDigest(bob.address(), max_amount, expiry, nonce)
+        .call()
+        .await?;
+    let signature = alice.sign_hash(&digest).await;
+    let v = signature.v().y_parity_byte() + 27;
+    let r = B256::from(signature.r());
+    let s = B256::from(signature.s());
+
+    // The voucher is bound to its terms and its buyer.
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenWithVoucher(
+            parse_ether("10").unwrap(),
+            arb_address,
+            parse_ether("50").unwrap(),
+            expiry,
+            nonce,
+            v,
+            r,
+            s,
+        )),
+        ITokenSale::InvalidSignature {}
+    ));
+    assert!(reverted_with(
+        send!(contract.buyTokenWithVoucher(
+            parse_ether("10").unwrap(),
+            arb_address,
+            max_amount,
+            expiry,
+            nonce,
+            v,
+            r,
+            s,
+        )),
+        ITokenSale::InvalidSignature {}
+    ));
+
+    // 12 ARB at 0.5 USD would buy 6 tokens, more than the voucher allows.
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenWithVoucher(
+            parse_ether("12").unwrap(),
+            arb_address,
+            max_amount,
+            expiry,
+            nonce,
+            v,
+            r,
+            s,
+        )),
+        ITokenSale::AllocationExceeded {}
+    ));
+
+    let _ = send!(contract_bob.buyTokenWithVoucher(
+        parse_ether("10").unwrap(),
+        arb_address,
+        max_amount,
+        expiry,
+        nonce,
+        v,
+        r,
+        s,
+    ))
+    .unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, max_amount);
+
+    let ITokenSale::isNonceUsedReturn { used } =
+        contract.isNonceUsed(bob.address(), nonce).call().await?;
+    assert!(used);
+
+    // Replaying the voucher fails.
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenWithVoucher(
+            parse_ether("10").unwrap(),
+            arb_address,
+            max_amount,
+            expiry,
+            nonce,
+            v,
+            r,
+            s,
+        )),
+        ITokenSale::VoucherUsed {}
+    ));
+
+    // Clearing the signer opens the sale to everyone again.
+    let _ = send!(contract.setVoucherSigner(Address::ZERO)).unwrap();
+    let _ = send!(contract_bob.buyToken(parse_ether("10").unwrap(), arb_address, false)).unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("10").unwrap());
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn contributions_respect_wallet_limits(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    assert!(reverted_with(
+        send!(contract.setContributionLimits(parse_ether("20").unwrap(), parse_ether("5").unwrap())),
+        ITokenSale::InvalidContributionLimits {}
+    ));
+
+    let _ = send!(
+        contract.setContributionLimits(parse_ether("5").unwrap(), parse_ether("20").unwrap())
+    )
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("100").unwrap())).unwrap();
+
+    // 8 ARB at 0.5 USD is below the 5 USD minimum.
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("8").unwrap(), arb_address, false)),
+        ITokenSale::BelowMinimum {}
+    ));
+
+    let _ = send!(contract_bob.buyToken(parse_ether("20").unwrap(), arb_address, false)).unwrap();
+
+    let ITokenSale::remainingAllocationReturn { remaining } =
+        contract.remainingAllocation(bob.address()).call().await?;
+    assert_eq!(remaining, parse_ether("10").unwrap());
+
+    // 15 USD more would take bob past the 20 USD maximum.
+    assert!(reverted_with(
+        send!(contract_bob.buyToken(parse_ether("30").unwrap(), arb_address, false)),
+        ITokenSale::AboveMaximum {}
+    ));
+
+    let _ = send!(contract_bob.buyToken(parse_ether("20").unwrap(), arb_address, false)).unwrap();
+
+    let ITokenSale::remainingAllocationReturn { remaining } =
+        contract.remainingAllocation(bob.address()).call().await?;
+    assert_eq!(remaining, U256::ZERO);
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("20").unwrap());
+
+    Ok(())
+}
+
+#[cfg(feature = "permit")]
+#[e2e::test]
+async fn it_can_be_bought_with_a_permit(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20_permit::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20PermitMock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+
+    let ERC20PermitMock::DOMAIN_SEPARATORReturn { separator } =
+        arb_contract.DOMAIN_SEPARATOR().call().await?;
+    let amount = parse_ether("10").unwrap();
+    let deadline = U256::from(latest_timestamp(&alice).await? + 1000);
+
+    let digest = erc20_permit::permit_digest(
+        separator,
+        bob.address(),
+        contract_addr,
+        amount,
+        U256::ZERO,
+        deadline,
+    );
+    let signature = bob.sign_hash(&digest).await;
+    let v = signature.v().y_parity_byte() + 27;
+    let r = B256::from(signature.r());
+    let s = B256::from(signature.s());
+
+    // Bob never approved the sale, the permit does it in the same transaction.
+    let _ = send!(contract_bob.buyTokenWithPermit(amount, arb_address, deadline, v, r, s)).unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("5").unwrap());
+
+    // The permit is spent and so is the allowance it granted.
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenWithPermit(amount, arb_address, deadline, v, r, s)),
+        ITokenSale::PermitFailed { token: arb_address }
+    ));
+
+    let digest = erc20_permit::permit_digest(
+        separator,
+        bob.address(),
+        contract_addr,
+        amount,
+        U256::from(1),
+        deadline,
+    );
+    let signature = bob.sign_hash(&digest).await;
+    let v = signature.v().y_parity_byte() + 27;
+    let r = B256::from(signature.r());
+    let s = B256::from(signature.s());
+
+    // Alice front-runs the permit, which must not block bob's purchase.
+    let _ = send!(arb_contract.permit(bob.address(), contract_addr, amount, deadline, v, r, s))
+        .unwrap();
+    let _ = send!(contract_bob.buyTokenWithPermit(amount, arb_address, deadline, v, r, s)).unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("10").unwrap());
+
+    Ok(())
+}
+
+#[cfg(feature = "permit2")]
+#[e2e::test]
+async fn it_can_be_bought_through_permit2(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+    let permit2_address = permit2::deploy(&alice.wallet).await?;
+    let permit2_contract = Permit2Mock::new(permit2_address, &alice.wallet);
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let _ = send!(contract.setPermit2(permit2_address)).unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    // Bob's only approval is the blanket one to Permit2.
+    let _ = send!(arb_contract_bob.approve(permit2_address, parse_ether(MINT_AMOUNT).unwrap()))
+        .unwrap();
+
+    let Permit2Mock::DOMAIN_SEPARATORReturn { separator } =
+        permit2_contract.DOMAIN_SEPARATOR().call().await?;
+    let amount = parse_ether("10").unwrap();
+    let nonce = U256::from(7);
+    let deadline = U256::from(latest_timestamp(&alice).await? + 1000);
+
+    let digest = permit2::permit_transfer_digest(
+        separator,
+        arb_address,
+        amount,
+        contract_addr,
+        nonce,
+        deadline,
+    );
+    let signature = bob.sign_hash(&digest).await;
+    let signature = Bytes::from(
+        [
+            B256::from(signature.r()).as_slice(),
+            B256::from(signature.s()).as_slice(),
+            &[signature.v().y_parity_byte() + 27],
+        ]
+        .concat(),
+    );
+
+    // The permit only covers 10 ARB.
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenPermit2(
+            parse_ether("20").unwrap(),
+            arb_address,
+            false,
+            nonce,
+            deadline,
+            signature.clone(),
+        )),
+        ITokenSale::TransferFromFailed { token: arb_address }
+    ));
+
+    let _ = send!(contract_bob.buyTokenPermit2(
+        amount,
+        arb_address,
+        false,
+        nonce,
+        deadline,
+        signature.clone(),
+    ))
+    .unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("5").unwrap());
+
+    let contract_arb_bal = arb_contract.balanceOf(contract_addr).call().await?;
+    assert_eq!(contract_arb_bal.balance, amount);
+
+    // Signature transfer nonces cannot be replayed.
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenPermit2(amount, arb_address, false, nonce, deadline, signature,)),
+        ITokenSale::TransferFromFailed { token: arb_address }
+    ));
+
+    // Nor can the Permit2 contract be swapped out under buyers.
+    assert!(reverted_with(
+        send!(contract.setPermit2(Address::ZERO)),
+        ITokenSale::SaleAlreadyStarted {}
+    ));
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn fee_on_transfer_tokens_pay_for_what_arrives(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20_taxed::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20TaxedMock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20TaxedMock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("100").unwrap())).unwrap();
+
+    let _ = send!(contract_bob.buyToken(parse_ether("100").unwrap(), arb_address, false)).unwrap();
+
+    // Only 99 of the 100 ARB arrive, worth 49.5 USD at 0.5 USD.
+    let contract_arb_bal = arb_contract.balanceOf(contract_addr).call().await?;
+    assert_eq!(contract_arb_bal.balance, parse_ether("99").unwrap());
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("49.5").unwrap());
+
+    let ITokenSale::raisedUsdReturn { raisedUsd } = contract.raisedUsd().call().await?;
+    assert_eq!(raisedUsd, parse_ether("49.5").unwrap());
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn exact_purchases_respect_slippage_and_deadline(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("100").unwrap())).unwrap();
+
+    let now = latest_timestamp(&alice).await?;
+
+    // 20 ARB at 0.5 USD buys 10 tokens, short of the 11 bob asks for.
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenExact(
+            parse_ether("20").unwrap(),
+            arb_address,
+            parse_ether("11").unwrap(),
+            U256::from(now + 1000),
+        )),
+        ITokenSale::SlippageExceeded {
+            amount_out: parse_ether("10").unwrap(),
+            min_amount_out: parse_ether("11").unwrap()
+        }
+    ));
+
+    assert!(reverted_with(
+        send!(contract_bob.buyTokenExact(
+            parse_ether("20").unwrap(),
+            arb_address,
+            parse_ether("10").unwrap(),
+            U256::from(now - 1),
+        )),
+        ITokenSale::DeadlineExpired {}
+    ));
+
+    let _ = send!(contract_bob.buyTokenExact(
+        parse_ether("20").unwrap(),
+        arb_address,
+        parse_ether("10").unwrap(),
+        U256::from(now + 1000),
+    ))
+    .unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("10").unwrap());
+
+    Ok(())
+}
+
+#[e2e::test]
+async fn it_can_buy_an_exact_amount_of_tokens(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        0,
+        U256::ZERO,
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("100").unwrap())).unwrap();
+
+    // 10 tokens at 1 USD cost 20 ARB at 0.5 USD.
+    let ITokenSale::quoteAmountInReturn { amountIn } = contract
+        .quoteAmountIn(parse_ether("10").unwrap(), arb_address)
+        .call()
+        .await?;
+    assert_eq!(amountIn, parse_ether("20").unwrap());
+
+    assert!(reverted_with(
+        send!(contract_bob.buyExactTokens(
+            parse_ether("10").unwrap(),
+            arb_address,
+            parse_ether("19").unwrap(),
+        )),
+        ITokenSale::ExcessiveAmountIn {
+            amount_in: parse_ether("20").unwrap(),
+            max_amount_in: parse_ether("19").unwrap()
+        }
+    ));
+
+    let _ = send!(contract_bob.buyExactTokens(
+        parse_ether("10").unwrap(),
+        arb_address,
+        parse_ether("25").unwrap(),
+    ))
+    .unwrap();
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("10").unwrap());
+
+    let contract_arb_bal = arb_contract.balanceOf(contract_addr).call().await?;
+    assert_eq!(contract_arb_bal.balance, parse_ether("20").unwrap());
+
+    Ok(())
+}
+
+#[cfg(feature = "bonding-curves")]
+#[e2e::test]
+async fn exact_purchases_are_exact_on_the_exponential_curve(
+    alice: Account,
+    bob: Account,
+) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    // The price starts at 1 USD and grows by 1% per token sold.
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        2,
+        parse_ether("0.01").unwrap(),
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("100").unwrap())).unwrap();
+
+    for _ in 0..3 {
+        let _ = send!(contract_bob.buyExactTokens(
+            parse_ether("7").unwrap(),
+            arb_address,
+            parse_ether("30").unwrap(),
+        ))
+        .unwrap();
+    }
+
+    let bob_token_bal = token_contract.balanceOf(bob.address()).call().await?;
+    assert_eq!(bob_token_bal.balance, parse_ether("21").unwrap());
+
+    Ok(())
+}
+
+#[cfg(feature = "bonding-curves")]
+#[e2e::test]
+async fn quotes_and_sale_state_render_the_sale(alice: Account, bob: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+    let token_address = erc20::deploy(&alice.wallet).await?;
+    let token_contract = ERC20Mock::new(token_address, &alice.wallet);
+    let arb_address = erc20::deploy(&alice.wallet).await?;
+    let arb_contract = ERC20Mock::new(arb_address, &alice.wallet);
+    let oracle_address = oracle::deploy(&alice.wallet).await?;
+
+    let _ = send!(arb_contract.mint(bob.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.mint(alice.address(), parse_ether(MINT_AMOUNT).unwrap()));
+    let _ = send!(token_contract.approve(contract_addr, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap()));
+
+    // The price starts at 1 USD and rises by 0.02 USD per token sold.
+    let _ = send!(contract.initialise(
+        alice.address(),
+        token_address,
+        oracle_address,
+        parse_ether(ADMIN_TOTAL_SUPPLY).unwrap(),
+        parse_ether("1").unwrap(),
+        parse_ether("1").unwrap(),
+        U256::ZERO,
+        1,
+        parse_ether("0.02").unwrap(),
+        vec![arb_address],
+        vec![0],
+    ))
+    .unwrap();
+
+    // 22 ARB buys 10 tokens for 11 USD and moves the price to 1.2 USD.
+    let quote = contract
+        .quoteBuy(parse_ether("22").unwrap(), arb_address)
+        .call()
+        .await?;
+    assert_eq!(quote.amountOut, parse_ether("10").unwrap());
+    assert_eq!(quote.effectivePrice, parse_ether("1.1").unwrap());
+    assert_eq!(quote.postTradePrice, parse_ether("1.2").unwrap());
+    assert_eq!(quote.remainingSupply, parse_ether("990").unwrap());
+
+    // More than the whole supply cannot be quoted.
+    assert!(reverted_with(
+        contract
+            .quoteBuy(parse_ether("1000000").unwrap(), arb_address)
+            .call()
+            .await,
+        ITokenSale::SoldOut {}
+    ));
+
+    let contract_bob = ITokenSale::new(contract_addr, &bob.wallet);
+    let arb_contract_bob = ERC20Mock::new(arb_address, &bob.wallet);
+
+    let _ = send!(arb_contract_bob.approve(contract_addr, parse_ether("22").unwrap())).unwrap();
+    let _ = send!(contract_bob.buyToken(parse_ether("22").unwrap(), arb_address, false)).unwrap();
+
+    let ITokenSale::saleStateReturn { state } = contract.saleState().call().await?;
+    assert_eq!(state.admin, alice.address());
+    assert_eq!(state.token, token_address);
+    assert_eq!(state.oracle, oracle_address);
+    assert_eq!(state.totalSupply, parse_ether(ADMIN_TOTAL_SUPPLY).unwrap());
+    assert_eq!(state.tokensSold, parse_ether("10").unwrap());
+    assert_eq!(state.raisedUsd, parse_ether("11").unwrap());
+    assert_eq!(state.softCap, U256::ZERO);
+    assert_eq!(state.saleStart, U256::ZERO);
+    assert_eq!(state.saleEnd, parse_ether("1").unwrap());
+    assert_eq!(state.currentPrice, parse_ether("1.2").unwrap());
+
+    Ok(())
+}
+
+#[cfg(not(feature = "permit2"))]
+#[e2e::test]
+async fn features_left_out_of_the_build_are_rejected(alice: Account) -> Result<()> {
+    let contract_addr = alice.as_deployer().deploy().await?.address()?;
+    let contract = ITokenSale::new(contract_addr, &alice.wallet);
+
+    assert!(reverted_with(
+        send!(contract.setPermit2(Address::ZERO)),
+        ITokenSale::FeatureDisabled {}
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+#![allow(dead_code)]
+
+use alloy_primitives::Address;
+use e2e::{alloy::sol, eyre, Wallet};
+
+sol! {
+      #[sol(rpc, bytecode="61007980600a5f395ff360003560e01c8063313ce56714610036578063feaf968c1461004157806399213cd8146100695780631ecc7d881461007157600080fd5b600860005260206000f35b6001600052600054602052600154806100575750425b80604052606052600160805260a06000f35b600435600055005b60043560015500")]
+      contract AggregatorMock {
+          int256 private answer;
+          uint256 private updatedAt;
+
+          function decimals() public pure returns (uint8) {
+              return 8;
+          }
+
+          function setAnswer(int256 _answer) public {
+              answer = _answer;
+          }
+
+          function setUpdatedAt(uint256 _updatedAt) public {
+              updatedAt = _updatedAt;
+          }
+
+          function latestRoundData()
+              public
+              view
+              returns (uint80 roundId, int256 latestAnswer, uint256 startedAt, uint256 latestUpdatedAt, uint80 answeredInRound)
+          {
+              uint256 timestamp = updatedAt == 0 ? block.timestamp : updatedAt;
+              return (1, answer, timestamp, timestamp, 1);
+          }
+      }
+}
+
+pub async fn deploy(wallet: &Wallet) -> eyre::Result<Address> {
+    let contract = AggregatorMock::deploy(wallet).await?;
+    Ok(*contract.address())
+}
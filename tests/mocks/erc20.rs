@@ -30,3 +30,56 @@ pub async fn deploy(wallet: &Wallet) -> eyre::Result<Address> {
     let contract = ERC20Mock::deploy(wallet).await?;
     Ok(*contract.address())
 }
+
+sol! {
+    #[sol(rpc, bytecode="60206020380360003960005160ff1660025561019c80601e6000396000f360003560e01c806370a0823114610058578063dd62ed3e14610072578063313ce5671461009a57806340c10f19146100a6578063095ea7b3146100c0578063a9059cbb146100ec57806323b872dd1461012d575b600080fd5b600435600052600060205260406000205460005260206000f35b6024356004356000526001602052604060002060205260005260406000205460005260206000f35b60025460005260206000f35b600435600052600060205260406000208054602435019055005b600435336000526001602052604060002060205260005260406000206024359055600160005260206000f35b336000526000602052604060002080546024358181116100535790039055600435600052600060205260406000208054602435019055600160005260206000f35b33600435600052600160205260406000206020526000526040600020805460443581811161005357900390556004356000526000602052604060002080546044358181116100535790039055602435600052600060205260406000208054604435019055600160005260206000f3")]
+    contract ERC20DecimalsMock {
+        mapping(address => uint256) private _balances;
+        mapping(address => mapping(address => uint256)) private _allowances;
+        uint8 private _decimals;
+
+        constructor(uint8 decimals_) {
+            _decimals = decimals_;
+        }
+
+        function balanceOf(address account) public view returns (uint256 balance) {
+            return _balances[account];
+        }
+
+        function allowance(address owner, address spender) public view returns (uint256 allowance) {
+            return _allowances[owner][spender];
+        }
+
+        function decimals() public view returns (uint8) {
+            return _decimals;
+        }
+
+        function mint(address account, uint256 value) public {
+            _balances[account] += value;
+        }
+
+        function approve(address spender, uint256 value) public returns (bool) {
+            _allowances[msg.sender][spender] = value;
+            return true;
+        }
+
+        function transfer(address to, uint256 value) public returns (bool) {
+            _balances[msg.sender] -= value;
+            _balances[to] += value;
+            return true;
+        }
+
+        function transferFrom(address from, address to, uint256 value) public returns (bool) {
+            _allowances[from][msg.sender] -= value;
+            _balances[from] -= value;
+            _balances[to] += value;
+            return true;
+        }
+    }
+}
+
+/// Deploys an ERC20 mock reporting `decimals`.
+pub async fn deploy_with_decimals(wallet: &Wallet, decimals: u8) -> eyre::Result<Address> {
+    let contract = ERC20DecimalsMock::deploy(wallet, decimals).await?;
+    Ok(*contract.address())
+}
@@ -0,0 +1,116 @@
+#![allow(dead_code)]
+
+use alloy_primitives::{keccak256, Address, B256, U256};
+use e2e::{alloy::sol, eyre, Wallet};
+
+sol! {
+    #[sol(rpc, bytecode="6103cb80600a5f395ff360003560e01c806370a0823114610079578063dd62ed3e146100935780637ecebe00146100bb578063313ce567146100d557806340c10f19146100e0578063095ea7b3146100fa578063a9059cbb1461012657806323b872dd146101675780633644e515146101d6578063d505accf14610259575b600080fd5b600435600052600060205260406000205460005260206000f35b6024356004356000526001602052604060002060205260005260406000205460005260206000f35b600435600052600260205260406000205460005260206000f35b601260005260206000f35b600435600052600060205260406000208054602435019055005b600435336000526001602052604060002060205260005260406000206024359055600160005260206000f35b336000526000602052604060002080546024358181116100745790039055600435600052600060205260406000208054602435019055600160005260206000f35b33600435600052600160205260406000206020526000526040600020805460443581811161007457900390556004356000526000602052604060002080546044358181116100745790039055602435600052600060205260406000208054604435019055600160005260206000f35b7f8b73c3c69bb8fe3d512ecc4cf759cc79239f7b179b0ffacaa9a75d522b39400f6080527f8039067418115fbd72d0602c22a9c36131276d82299b607b9595667d59d47a1c60a0527fc89efdaa54c0f20c7adf612882df0950f5a951637e0307cdcb4c672f298b8bc660c0524660e052306101005260a060802060005260206000f35b426064351061007457600435600052600260205260406000208054806101005260010190557f6e71edae12b1b97f4d1f60370fef10105fa2faae0126114a169c64845d6126c960805260043560a05260243560c05260443560e0526064356101205260c06080207f8b73c3c69bb8fe3d512ecc4cf759cc79239f7b179b0ffacaa9a75d522b39400f6080527f8039067418115fbd72d0602c22a9c36131276d82299b607b9595667d59d47a1c60a0527fc89efdaa54c0f20c7adf612882df0950f5a951637e0307cdcb4c672f298b8bc660c0524660e052306101005260a06080207f1901000000000000000000000000000000000000000000000000000000000000600052600252602252604260002060805260843560a05260a43560c05260c43560e0526000600052602060006080608060015afa1561007457600051801561007457600435141561007457602435600435600052600160205260406000206020526000526040600020604435905500")]
+    contract ERC20PermitMock {
+        mapping(address => uint256) private _balances;
+        mapping(address => mapping(address => uint256)) private _allowances;
+        mapping(address => uint256) private _nonces;
+
+        function balanceOf(address account) public view returns (uint256 balance) {
+            return _balances[account];
+        }
+
+        function allowance(address owner, address spender) public view returns (uint256 allowance) {
+            return _allowances[owner][spender];
+        }
+
+        function nonces(address owner) public view returns (uint256 nonce) {
+            return _nonces[owner];
+        }
+
+        function decimals() public pure returns (uint8) {
+            return 18;
+        }
+
+        function mint(address account, uint256 value) public {
+            _balances[account] += value;
+        }
+
+        function approve(address spender, uint256 value) public returns (bool) {
+            _allowances[msg.sender][spender] = value;
+            return true;
+        }
+
+        function transfer(address to, uint256 value) public returns (bool) {
+            _balances[msg.sender] -= value;
+            _balances[to] += value;
+            return true;
+        }
+
+        function transferFrom(address from, address to, uint256 value) public returns (bool) {
+            _allowances[from][msg.sender] -= value;
+            _balances[from] -= value;
+            _balances[to] += value;
+            return true;
+        }
+
+        function DOMAIN_SEPARATOR() public view returns (bytes32 separator) {
+            return keccak256(abi.encode(
+                keccak256("EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)"),
+                keccak256("ERC20PermitMock"),
+                keccak256("1"),
+                block.chainid,
+                address(this)
+            ));
+        }
+
+        function permit(address owner, address spender, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s) public {
+            require(block.timestamp <= deadline);
+            bytes32 structHash = keccak256(abi.encode(
+                keccak256("Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)"),
+                owner,
+                spender,
+                value,
+                _nonces[owner]++,
+                deadline
+            ));
+            bytes32 digest = keccak256(abi.encodePacked("\x19\x01", DOMAIN_SEPARATOR(), structHash));
+            address signer = ecrecover(digest, v, r, s);
+            require(signer != address(0) && signer == owner);
+            _allowances[owner][spender] = value;
+        }
+    }
+}
+
+pub async fn deploy(wallet: &Wallet) -> eyre::Result<Address> {
+    let contract = ERC20PermitMock::deploy(wallet).await?;
+    Ok(*contract.address())
+}
+
+/// The EIP-712 digest `owner` signs to let `spender` pull `value` tokens.
+pub fn permit_digest(
+    domain_separator: B256,
+    owner: Address,
+    spender: Address,
+    value: U256,
+    nonce: U256,
+    deadline: U256,
+) -> B256 {
+    let typehash = keccak256(
+        "Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)",
+    );
+    let struct_hash = keccak256(
+        [
+            typehash.as_slice(),
+            owner.into_word().as_slice(),
+            spender.into_word().as_slice(),
+            &value.to_be_bytes::<32>(),
+            &nonce.to_be_bytes::<32>(),
+            &deadline.to_be_bytes::<32>(),
+        ]
+        .concat(),
+    );
+
+    keccak256(
+        [
+            b"\x19\x01".as_slice(),
+            domain_separator.as_slice(),
+            struct_hash.as_slice(),
+        ]
+        .concat(),
+    )
+}
@@ -0,0 +1,52 @@
+#![allow(dead_code)]
+
+use alloy_primitives::Address;
+use e2e::{alloy::sol, eyre, Wallet};
+
+sol! {
+    #[sol(rpc, bytecode="6101a980600a5f395ff360003560e01c806370a0823114610058578063dd62ed3e14610072578063313ce5671461009a57806340c10f19146100a5578063095ea7b3146100bf578063a9059cbb146100eb57806323b872dd14610133575b600080fd5b600435600052600060205260406000205460005260206000f35b6024356004356000526001602052604060002060205260005260406000205460005260206000f35b601260005260206000f35b600435600052600060205260406000208054602435019055005b600435336000526001602052604060002060205260005260406000206024359055600160005260206000f35b33600052600060205260406000208054602435818111610053579003905560043560005260006020526040600020805460243580606490049003019055600160005260206000f35b3360043560005260016020526040600020602052600052604060002080546044358181116100535790039055600435600052600060205260406000208054604435818111610053579003905560243560005260006020526040600020805460443580606490049003019055600160005260206000f3")]
+    contract ERC20TaxedMock {
+        mapping(address => uint256) private _balances;
+        mapping(address => mapping(address => uint256)) private _allowances;
+
+        function balanceOf(address account) public view returns (uint256 balance) {
+            return _balances[account];
+        }
+
+        function allowance(address owner, address spender) public view returns (uint256 allowance) {
+            return _allowances[owner][spender];
+        }
+
+        function decimals() public pure returns (uint8) {
+            return 18;
+        }
+
+        function mint(address account, uint256 value) public {
+            _balances[account] += value;
+        }
+
+        function approve(address spender, uint256 value) public returns (bool) {
+            _allowances[msg.sender][spender] = value;
+            return true;
+        }
+
+        function transfer(address to, uint256 value) public returns (bool) {
+            _balances[msg.sender] -= value;
+            _balances[to] += value - value / 100;
+            return true;
+        }
+
+        // Burns 1% of every transfer.
+        function transferFrom(address from, address to, uint256 value) public returns (bool) {
+            _allowances[from][msg.sender] -= value;
+            _balances[from] -= value;
+            _balances[to] += value - value / 100;
+            return true;
+        }
+    }
+}
+
+pub async fn deploy(wallet: &Wallet) -> eyre::Result<Address> {
+    let contract = ERC20TaxedMock::deploy(wallet).await?;
+    Ok(*contract.address())
+}
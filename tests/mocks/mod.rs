@@ -1,2 +1,6 @@
+pub mod aggregator;
 pub mod erc20;
+pub mod erc20_permit;
+pub mod erc20_taxed;
 pub mod oracle;
+pub mod permit2;
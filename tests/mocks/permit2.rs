@@ -0,0 +1,127 @@
+#![allow(dead_code)]
+
+use alloy_primitives::{keccak256, Address, B256, U256};
+use e2e::{alloy::sol, eyre, Wallet};
+
+sol! {
+    #[sol(rpc, bytecode="61029980600a5f395ff360003560e01c806330f28b7a146100b25780633644e5151461002c5780631647795e1461008a575b600080fd5b7f8cad95687ba82c2ce50e74f7b754645e5117c3a5bec8151c0726d5857980a8666080527f9ac997416e8ff9d2ff6bebeb7149f65cdae5e32e2b90440b566bb3044041d36a60a0524660c0523060e052608060802060005260206000f35b6024356004356000526000602052604060002060205260005260406000205460005260206000f35b42606435106100275760243560a435116100275760443560c435600052600060205260406000206020526000526040600020805461002757600190557f618358ac3db8dc274f0cd8829da7e234bd48cd73c4a740aede1adec9846d06a160805260043560a05260243560c05260606080207f939c21a48a8dbe3a9a2404a1d46691e4d39f6583d6ec6b35714604c986d8010660805260a0523360c05260443560e0526064356101005260a06080207f8cad95687ba82c2ce50e74f7b754645e5117c3a5bec8151c0726d5857980a8666080527f9ac997416e8ff9d2ff6bebeb7149f65cdae5e32e2b90440b566bb3044041d36a60a0524660c0523060e05260806080207f1901000000000000000000000000000000000000000000000000000000000000600052600252602252604260002060805260e43560040180356041141561002757806020013560c052806040013560e0526060013560f81c60a0526000600052602060006080608060015afa156100275760005180156100275760c4351415610027577f23b872dd0000000000000000000000000000000000000000000000000000000060805260c43560845260843560a45260a43560c4526000600052602060006064608060006004355af115610027573d156102975760005115610027575b00")]
+    contract Permit2Mock {
+        struct TokenPermissions {
+            address token;
+            uint256 amount;
+        }
+
+        struct PermitTransferFrom {
+            TokenPermissions permitted;
+            uint256 nonce;
+            uint256 deadline;
+        }
+
+        struct SignatureTransferDetails {
+            address to;
+            uint256 requestedAmount;
+        }
+
+        mapping(address => mapping(uint256 => bool)) private _nonceUsed;
+
+        function DOMAIN_SEPARATOR() public view returns (bytes32 separator) {
+            return keccak256(abi.encode(
+                keccak256("EIP712Domain(string name,uint256 chainId,address verifyingContract)"),
+                keccak256("Permit2"),
+                block.chainid,
+                address(this)
+            ));
+        }
+
+        function nonceUsed(address owner, uint256 nonce) public view returns (bool used) {
+            return _nonceUsed[owner][nonce];
+        }
+
+        function permitTransferFrom(
+            PermitTransferFrom memory permit,
+            SignatureTransferDetails calldata transferDetails,
+            address owner,
+            bytes calldata signature
+        ) public {
+            require(block.timestamp <= permit.deadline);
+            require(transferDetails.requestedAmount <= permit.permitted.amount);
+            require(!_nonceUsed[owner][permit.nonce]);
+            _nonceUsed[owner][permit.nonce] = true;
+
+            bytes32 tokenPermissions = keccak256(abi.encode(
+                keccak256("TokenPermissions(address token,uint256 amount)"),
+                permit.permitted.token,
+                permit.permitted.amount
+            ));
+            bytes32 structHash = keccak256(abi.encode(
+                keccak256("PermitTransferFrom(TokenPermissions permitted,address spender,uint256 nonce,uint256 deadline)TokenPermissions(address token,uint256 amount)"),
+                tokenPermissions,
+                msg.sender,
+                permit.nonce,
+                permit.deadline
+            ));
+            bytes32 digest = keccak256(abi.encodePacked("\x19\x01", DOMAIN_SEPARATOR(), structHash));
+
+            require(signature.length == 65);
+            (bytes32 r, bytes32 s) = abi.decode(signature[:64], (bytes32, bytes32));
+            address signer = ecrecover(digest, uint8(signature[64]), r, s);
+            require(signer != address(0) && signer == owner);
+
+            // Tokens returning no data count as success, like solmate's SafeTransferLib.
+            (bool success, bytes memory data) = permit.permitted.token.call(abi.encodeWithSignature(
+                "transferFrom(address,address,uint256)",
+                owner,
+                transferDetails.to,
+                transferDetails.requestedAmount
+            ));
+            require(success && (data.length == 0 || abi.decode(data, (bool))));
+        }
+    }
+}
+
+pub async fn deploy(wallet: &Wallet) -> eyre::Result<Address> {
+    let contract = Permit2Mock::deploy(wallet).await?;
+    Ok(*contract.address())
+}
+
+/// The EIP-712 digest `owner` signs to let `spender` pull up to `amount`
+/// of `token` once.
+pub fn permit_transfer_digest(
+    domain_separator: B256,
+    token: Address,
+    amount: U256,
+    spender: Address,
+    nonce: U256,
+    deadline: U256,
+) -> B256 {
+    let token_permissions = keccak256(
+        [
+            keccak256("TokenPermissions(address token,uint256 amount)").as_slice(),
+            token.into_word().as_slice(),
+            &amount.to_be_bytes::<32>(),
+        ]
+        .concat(),
+    );
+    let typehash = keccak256(
+        "PermitTransferFrom(TokenPermissions permitted,address spender,uint256 nonce,uint256 deadline)TokenPermissions(address token,uint256 amount)",
+    );
+    let struct_hash = keccak256(
+        [
+            typehash.as_slice(),
+            token_permissions.as_slice(),
+            spender.into_word().as_slice(),
+            &nonce.to_be_bytes::<32>(),
+            &deadline.to_be_bytes::<32>(),
+        ]
+        .concat(),
+    );
+
+    keccak256(
+        [
+            b"\x19\x01".as_slice(),
+            domain_separator.as_slice(),
+            struct_hash.as_slice(),
+        ]
+        .concat(),
+    )
+}